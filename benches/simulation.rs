@@ -0,0 +1,81 @@
+//! How long a tick of the burn takes at different sizes. At 60 fps a frame has about 16ms, all in; a 400x120 page
+//! should step and draw in a small fraction of that.
+
+#[macro_use]
+extern crate criterion;
+extern crate burn;
+
+use criterion::{BatchSize, Criterion, Throughput};
+
+use burn::color_depth::ColorDepth;
+use burn::config::Config;
+use burn::layers::Compositor;
+use burn::scene::Scene;
+use burn::state::SmokeModel;
+use burn::theme::Theme;
+
+const SIZES: &[(usize, usize)] = &[(80, 24), (400, 120)];
+
+/// Ticks per measurement: from the fire being lit to well after it has spread over a large page, so that quiet and
+/// busy stretches of the burn are both counted.
+const TICKS: u64 = 150;
+
+fn scene(cols: usize, rows: usize, smoke_model: SmokeModel) -> Scene {
+    let mut config = Config::default();
+    config.simulation.smoke_model = smoke_model;
+    config.rendering.flicker = true;
+
+    let line = "the quick brown fox jumps over the lazy dog ".repeat(cols / 40 + 1);
+    let lines = vec![line; rows];
+
+    Scene::new(lines, rows, cols, Theme::default(), &config, 1).unwrap()
+}
+
+fn bench_step(c: &mut Criterion) {
+    let mut group = c.benchmark_group("step");
+    group.throughput(Throughput::Elements(TICKS));
+    group.sample_size(20);
+
+    for &(cols, rows) in SIZES {
+        for &(name, model) in &[("fluid", SmokeModel::Fluid), ("particles", SmokeModel::Particles)] {
+            group.bench_function(format!("{}x{}/{}", cols, rows, name), |b| {
+                b.iter_batched(|| scene(cols, rows, model), |mut scene| {
+                    for _ in 0..TICKS {
+                        scene.step();
+                    }
+                    scene
+                }, BatchSize::LargeInput)
+            });
+        }
+    }
+
+    group.finish();
+}
+
+/// A frame as `burn` draws it: one tick, then the layers composited into escape codes, reusing the last frame's lines.
+fn bench_frame(c: &mut Criterion) {
+    let mut group = c.benchmark_group("frame");
+    group.throughput(Throughput::Elements(TICKS));
+    group.sample_size(20);
+
+    for &(cols, rows) in SIZES {
+        let compositor = Compositor { rows, cols, color_depth: ColorDepth::TrueColor };
+
+        group.bench_function(format!("{}x{}", cols, rows), |b| {
+            let mut field = vec![];
+
+            b.iter_batched(|| scene(cols, rows, SmokeModel::Fluid), |mut scene| {
+                for _ in 0..TICKS {
+                    scene.step();
+                    scene.composite(|layers| compositor.composite_into(layers, &mut field));
+                }
+                scene
+            }, BatchSize::LargeInput)
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_step, bench_frame);
+criterion_main!(benches);
@@ -0,0 +1,89 @@
+use std::env;
+use std::io::{self, Write};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use json;
+
+/// AsciicastWriter records everything written to it as an asciinema asciicast (v2) on `out`. Output is
+/// buffered until the next flush and then written as a single event, so each frame drawn by `Ui` becomes
+/// one event stamped with the time it was drawn.
+pub struct AsciicastWriter<W: Write> {
+    out: W,
+    started: Instant,
+    pending: Vec<u8>,
+}
+
+impl<W: Write> AsciicastWriter<W> {
+    /// new writes the recording's header. `cols` and `rows` give the size of the terminal the recording
+    /// is played back in, which need not match the terminal `burn` is running in.
+    pub fn new(mut out: W, cols: usize, rows: usize) -> io::Result<Self> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|since| since.as_secs()).unwrap_or(0);
+        let term = env::var("TERM").unwrap_or_else(|_| "xterm-256color".to_string());
+
+        writeln!(
+            out,
+            "{{\"version\": 2, \"width\": {}, \"height\": {}, \"timestamp\": {}, \"env\": {{\"TERM\": {}}}}}",
+            cols, rows, timestamp, json::string(&term),
+        )?;
+
+        Ok(AsciicastWriter {
+            out,
+            started: Instant::now(),
+            pending: vec![],
+        })
+    }
+}
+
+impl<W: Write> Write for AsciicastWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.pending.is_empty() {
+            let elapsed = self.started.elapsed();
+            let data = String::from_utf8_lossy(&self.pending).into_owned();
+
+            writeln!(self.out, "[{:.6}, \"o\", {}]", elapsed.as_secs_f64(), json::string(&data))?;
+            self.pending.clear();
+        }
+
+        self.out.flush()
+    }
+}
+
+impl<W: Write> Drop for AsciicastWriter<W> {
+    fn drop(&mut self) {
+        // anything written after the last frame, like showing the cursor again, still belongs in the recording
+        let _ = self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AsciicastWriter;
+    use std::io::Write;
+
+    #[test]
+    fn test_records_one_event_per_flush() {
+        let mut cast = vec![];
+
+        {
+            let mut writer = AsciicastWriter::new(&mut cast, 80, 24).unwrap();
+            writer.write_all(b"\x1b[1;1Hab").unwrap();
+            writer.write_all("\"🔥\"\r\n".as_bytes()).unwrap();
+            writer.flush().unwrap();
+            writer.flush().unwrap();
+            writer.write_all(b"\x1b[?25h").unwrap();
+        }
+
+        let cast = String::from_utf8(cast).unwrap();
+        let lines: Vec<&str> = cast.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("{\"version\": 2, \"width\": 80, \"height\": 24, "));
+        assert!(lines[1].starts_with('[') && lines[1].ends_with(", \"o\", \"\\u001b[1;1Hab\\\"🔥\\\"\\r\\n\"]"));
+        assert!(lines[2].ends_with(", \"o\", \"\\u001b[?25h\"]"));
+    }
+}
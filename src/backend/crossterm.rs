@@ -0,0 +1,114 @@
+use std::cell::{Cell as Flag, RefCell};
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Once;
+use std::time::Duration;
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::style::{Color, Print, ResetColor, SetForegroundColor};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+
+use grid::Grid;
+use layers::Cell;
+
+use super::Backend;
+
+static INSTALL_PANIC_HOOK: Once = Once::new();
+
+/// Set while a `CrosstermBackend` has the terminal.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Draws frames full screen on the terminal through crossterm, in true color. The terminal is in raw mode meanwhile,
+/// so Ctrl-C, Esc and `q` are read as keys that interrupt the burn rather than killing the process. Everything is put
+/// back when the backend is dropped, or if anything panics.
+pub struct CrosstermBackend<W: Write> {
+    writer: W,
+    interrupted: Flag<bool>,
+    /// Other keys typed, waiting to be read.
+    keys: RefCell<VecDeque<char>>,
+}
+
+impl<W: Write> CrosstermBackend<W> {
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        INSTALL_PANIC_HOOK.call_once(|| {
+            let previous = panic::take_hook();
+            panic::set_hook(Box::new(move |info| {
+                restore(&mut io::stdout());
+                previous(info);
+            }));
+        });
+
+        terminal::enable_raw_mode()?;
+        ACTIVE.store(true, Ordering::SeqCst);
+        execute!(writer, EnterAlternateScreen, Hide, Clear(ClearType::All))?;
+
+        Ok(CrosstermBackend { writer, interrupted: Flag::new(false), keys: RefCell::new(VecDeque::new()) })
+    }
+}
+
+impl<W: Write> Backend for CrosstermBackend<W> {
+    fn draw(&mut self, frame: &Grid<Cell>) -> io::Result<()> {
+        for (i, row) in frame.iter_rows().enumerate() {
+            queue!(self.writer, MoveTo(0, i as u16))?;
+
+            for cell in row {
+                match cell.fg {
+                    Some(fg) => queue!(self.writer, SetForegroundColor(Color::Rgb { r: fg.0, g: fg.1, b: fg.2 }), Print(cell.glyph), ResetColor)?,
+                    None => queue!(self.writer, Print(cell.glyph))?,
+                }
+            }
+        }
+
+        self.writer.flush()
+    }
+
+    fn is_interrupted(&self) -> bool {
+        self.read_events();
+        self.interrupted.get()
+    }
+
+    fn read_key(&mut self) -> Option<char> {
+        self.read_events();
+        self.keys.borrow_mut().pop_front()
+    }
+}
+
+impl<W: Write> CrosstermBackend<W> {
+    /// read_events goes through the keys typed since it was last called, noting whether any of them interrupt the burn
+    /// and queueing up the rest.
+    fn read_events(&self) {
+        while !self.interrupted.get() && event::poll(Duration::ZERO).unwrap_or(false) {
+            if let Ok(Event::Key(KeyEvent { code, modifiers, .. })) = event::read() {
+                let ctrl_c = code == KeyCode::Char('c') && modifiers.contains(KeyModifiers::CONTROL);
+                self.interrupted.set(ctrl_c || code == KeyCode::Esc || code == KeyCode::Char('q'));
+
+                if let KeyCode::Char(key) = code {
+                    if !self.interrupted.get() {
+                        self.keys.borrow_mut().push_back(key);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<W: Write> Drop for CrosstermBackend<W> {
+    fn drop(&mut self) {
+        restore(&mut self.writer);
+    }
+}
+
+/// restore takes the terminal out of raw mode and the alternate screen, if a `CrosstermBackend` still has it.
+fn restore<W: Write>(writer: &mut W) {
+    if !ACTIVE.swap(false, Ordering::SeqCst) {
+        return;
+    }
+
+    // the terminal may have gone away, in which case there's nothing left to put back
+    let _ = execute!(writer, ResetColor, Show, LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
+}
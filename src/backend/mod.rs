@@ -0,0 +1,80 @@
+//! Where frames of a burn end up. `ui::Ui` draws them on a terminal with termion, `CrosstermBackend` (with the
+//! `crossterm` feature) does the same with crossterm, and `MemoryBackend` keeps them, so that what was drawn can be
+//! checked without a terminal.
+
+#[cfg(feature = "crossterm")]
+mod crossterm;
+
+use std::collections::VecDeque;
+use std::io;
+
+use grid::Grid;
+use layers::Cell;
+
+#[cfg(feature = "crossterm")]
+pub use self::crossterm::CrosstermBackend;
+
+/// Something frames can be drawn to.
+pub trait Backend {
+    /// draw shows `frame`, replacing whatever frame was shown before.
+    fn draw(&mut self, frame: &Grid<Cell>) -> io::Result<()>;
+
+    /// is_interrupted is whether the user has asked for the burn to stop. Backends that can't be interrupted never
+    /// are.
+    fn is_interrupted(&self) -> bool {
+        false
+    }
+
+    /// read_key is the next key the user has pressed that hasn't been read yet, if any. Only keys that type a
+    /// character are reported. Backends without a keyboard never have any.
+    fn read_key(&mut self) -> Option<char> {
+        None
+    }
+}
+
+/// Keeps every frame drawn to it, in order, and plays back keys given to `press` as if they'd been typed.
+#[derive(Default)]
+pub struct MemoryBackend {
+    frames: Vec<Grid<Cell>>,
+    keys: VecDeque<char>,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn frames(&self) -> &[Grid<Cell>] {
+        &self.frames
+    }
+
+    /// press queues `key` to be read, after any pressed before it.
+    pub fn press(&mut self, key: char) {
+        self.keys.push_back(key);
+    }
+}
+
+impl Backend for MemoryBackend {
+    fn draw(&mut self, frame: &Grid<Cell>) -> io::Result<()> {
+        self.frames.push(frame.clone());
+        Ok(())
+    }
+
+    fn read_key(&mut self) -> Option<char> {
+        self.keys.pop_front()
+    }
+}
+
+impl<B: Backend + ?Sized> Backend for &mut B {
+    fn draw(&mut self, frame: &Grid<Cell>) -> io::Result<()> {
+        (**self).draw(frame)
+    }
+
+    fn is_interrupted(&self) -> bool {
+        (**self).is_interrupted()
+    }
+
+    fn read_key(&mut self) -> Option<char> {
+        (**self).read_key()
+    }
+}
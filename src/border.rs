@@ -1,46 +1,46 @@
-use layers::Layerable;
+use grid::Grid;
+use layers::{Cell, Layerable};
+use theme::BorderStyle;
 
+#[derive(Clone)]
 pub struct Border {
-    rows: usize,
-    cols: usize,
-    features: Vec<Vec<Option<String>>>
+    features: Grid<Option<Cell>>,
 }
 
 impl Border {
-    pub fn new(rows: usize, cols: usize) -> Self {
-        let mut features: Vec<Vec<Option<String>>> =
-            vec![vec![None; cols]; rows];
-
-        for cell in (&mut features[0]).into_iter() {
-            *cell = Some("━".into());
-        }
-        for cell in (&mut features[rows - 1]).into_iter() {
-            *cell = Some("━".into());
+    /// new draws a border around the edge of a page `rows` by `cols` cells, which must be at least 3 by 3 to leave room
+    /// inside.
+    pub fn new(rows: usize, cols: usize, style: &BorderStyle) -> Result<Self, String> {
+        if rows < 3 || cols < 3 {
+            return Err(format!("a page {}x{} is too small for a border, which needs at least 3x3", cols, rows));
         }
-        for cell in (&mut features).into_iter().map(|row| &mut row[0]) {
-            *cell = Some("┃".into());
+
+        let mut features = Grid::new(rows, cols, None);
+
+        for j in 0..cols {
+            features[(0, j)] = Some(style.cell(style.horizontal));
+            features[(rows - 1, j)] = Some(style.cell(style.horizontal));
         }
-        for cell in (&mut features).into_iter().map(|row| &mut row[cols - 1]) {
-            *cell = Some("┃".into());
+        for i in 0..rows {
+            features[(i, 0)] = Some(style.cell(style.vertical));
+            features[(i, cols - 1)] = Some(style.cell(style.vertical));
         }
 
-        features[0][0] = Some("┏".into());
-        features[0][cols - 1] = Some("┓".into());
-        features[rows - 1][0] = Some("┗".into());
-        features[rows - 1][cols - 1] = Some("┛".into());
+        features[(0, 0)] = Some(style.cell(style.top_left));
+        features[(0, cols - 1)] = Some(style.cell(style.top_right));
+        features[(rows - 1, 0)] = Some(style.cell(style.bottom_left));
+        features[(rows - 1, cols - 1)] = Some(style.cell(style.bottom_right));
 
-        Self {
-            rows,
-            cols,
+        Ok(Self {
             features
-        }
+        })
     }
 }
 
 impl Layerable for Border {
-    fn rows(&self) -> usize { self.rows }
-    fn cols(&self) -> usize { self.cols }
-    fn features(&self) -> &Vec<Vec<Option<String>>> {
+    fn rows(&self) -> usize { self.features.rows() }
+    fn cols(&self) -> usize { self.features.cols() }
+    fn features(&self) -> &Grid<Option<Cell>> {
         &self.features
     }
-}
\ No newline at end of file
+}
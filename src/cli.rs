@@ -0,0 +1,393 @@
+use std::env;
+use std::time::Duration;
+
+use burn::color_depth::ColorDepth;
+use burn::config::Config;
+use burn::export::Format;
+use burn::outro::Outro;
+use burn::sim::StatsFormat;
+use burn::state::SmokeModel;
+
+const USAGE: &str = "usage: `burn [--inline[=ROWS]] [--duration 10s] [options] <file>`, `burn record [--size COLSxROWS] [options] <out.cast> <file>` \
+                     to record an asciicast, `burn export [--gif|--apng|--html] [--size COLSxROWS] [options] <out> <file>` \
+                     to write an animation, `burn sim [--size COLSxROWS] [--frames N] [--csv] [--final-frame <out.txt>] \
+                     [options] [file]` to print statistics without a terminal, or `burn config --print [options]` to show \
+                     the effective settings.";
+
+/// What `burn` was asked to do.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Burn { filepath: String },
+    /// Burn the file into an asciicast at `output` rather than on the terminal.
+    Record { output: String, filepath: String },
+    /// Burn the file into an animation at `output`, without a terminal.
+    Export { output: String, filepath: String, format: Format },
+    /// Run the simulation without a terminal and print per-frame statistics. The page is blank unless a file is given.
+    /// Runs for `frames` frames, or until the burn is over, and writes the last frame as text to `final_frame`.
+    Sim { filepath: Option<String>, frames: Option<usize>, format: StatsFormat, final_frame: Option<String> },
+    /// Print the settings that would be used, after layering the config file, env vars and flags.
+    PrintConfig,
+}
+
+/// How many rows `burn --inline` sets aside.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum InlineRows {
+    /// Just enough for the file, border included.
+    Fit,
+    Exactly(usize),
+}
+
+/// Command-line options. Everything besides `command` and `delete` overrides a setting from the config file, and is
+/// left unset (or `false`) when its flag isn't given.
+pub struct Options {
+    pub command: Command,
+    pub translucent_smoke: bool,
+    pub flicker: bool,
+    /// Delete the file once it has burned, rather than only burning it for show. Only applies to `burn`, and has no
+    /// setting, so a config file or env var can't make every burn destructive.
+    pub delete: bool,
+    pub smoke_model: Option<SmokeModel>,
+    pub wind: Option<f64>,
+    /// A `color` setting, e.g. `auto` or `256`.
+    pub color: Option<String>,
+    /// A theme name or path to a theme file.
+    pub theme: Option<String>,
+    /// Size of the terminal to record for, as (columns, rows). Only applies to `burn record`, `burn export` and
+    /// `burn sim`.
+    pub size: Option<(usize, usize)>,
+    /// Seeds the simulation, so that the same burn can be played again.
+    pub seed: Option<u64>,
+    /// The most frames drawn a second.
+    pub fps: Option<u32>,
+    pub outro: Option<Outro>,
+    /// About how long the burn should take, which the ticks are stretched or squeezed to fit as it plays. Only applies
+    /// to `burn`, `burn record` and `burn export`.
+    pub duration: Option<Duration>,
+    /// Burn below the cursor instead of taking over the screen. Only applies to `burn`.
+    pub inline: Option<InlineRows>,
+}
+
+impl Options {
+    /// parse reads options from the process's arguments.
+    pub fn parse() -> Result<Self, &'static str> {
+        Self::parse_from(env::args().skip(1))
+    }
+
+    /// apply overrides settings in `config` with those given on the command line.
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(smoke_model) = self.smoke_model {
+            config.simulation.smoke_model = smoke_model;
+        }
+        if let Some(wind) = self.wind {
+            config.simulation.wind = wind;
+        }
+        if let Some(ref theme) = self.theme {
+            config.rendering.theme = theme.clone();
+        }
+        if let Some(ref color) = self.color {
+            config.rendering.color = color.clone();
+        }
+        if let Some(outro) = self.outro {
+            config.rendering.outro = outro;
+        }
+        if let Some(fps) = self.fps {
+            config.rendering.fps = fps;
+        }
+
+        config.rendering.flicker |= self.flicker;
+        config.rendering.translucent_smoke |= self.translucent_smoke;
+    }
+
+    /// parse_from reads options from `args`, which should not include the program name.
+    /// Options taking a value accept it either as `--option value` or `--option=value`.
+    pub fn parse_from<I: IntoIterator<Item = String>>(args: I) -> Result<Self, &'static str> {
+        let mut positional = vec![];
+        let mut print = false;
+        let mut translucent_smoke = false;
+        let mut flicker = false;
+        let mut delete = false;
+        let mut smoke_model = None;
+        let mut wind = None;
+        let mut color = None;
+        let mut theme = None;
+        let mut size = None;
+        let mut format = None;
+        let mut seed = None;
+        let mut frames = None;
+        let mut stats_format = None;
+        let mut final_frame = None;
+        let mut fps = None;
+        let mut duration = None;
+        let mut outro = None;
+        let mut inline = None;
+
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            if !arg.starts_with("--") {
+                positional.push(arg);
+                continue;
+            }
+
+            let (flag, inline_value) = match arg.find('=') {
+                Some(ix) => (arg[..ix].to_string(), Some(arg[ix + 1..].to_string())),
+                None => (arg.clone(), None),
+            };
+
+            let mut value = || inline_value.clone().or_else(|| args.next()).ok_or("missing value for option");
+
+            match flag.as_str() {
+                "--print" => print = true,
+                "--gif" => format = Some(Format::Gif),
+                "--apng" => format = Some(Format::Apng),
+                "--html" => format = Some(Format::Html),
+                "--json" => stats_format = Some(StatsFormat::Json),
+                "--csv" => stats_format = Some(StatsFormat::Csv),
+                "--translucent-smoke" => translucent_smoke = true,
+                "--flicker" => flicker = true,
+                "--delete" => delete = true,
+                // the number of rows is optional, so it can only be given as `--inline=ROWS`
+                "--inline" => {
+                    inline = match inline_value.as_deref().map(str::parse) {
+                        None => Some(InlineRows::Fit),
+                        Some(Ok(rows)) if rows >= 3 => Some(InlineRows::Exactly(rows)),
+                        Some(_) => return Err("`--inline` should be given a whole number of rows, at least 3"),
+                    }
+                },
+                "--smoke" => {
+                    smoke_model = match value()?.as_str() {
+                        "particles" => Some(SmokeModel::Particles),
+                        "fluid" => Some(SmokeModel::Fluid),
+                        _ => return Err("`--smoke` should be one of `particles` or `fluid`"),
+                    }
+                },
+                "--outro" => {
+                    outro = match value()?.as_str() {
+                        "none" => Some(Outro::None),
+                        "hold" => Some(Outro::Hold),
+                        "fade" => Some(Outro::Fade),
+                        "summary" => Some(Outro::Summary),
+                        _ => return Err("`--outro` should be one of `none`, `hold`, `fade` or `summary`"),
+                    }
+                },
+                "--wind" => wind = Some(value()?.parse().map_err(|_| "`--wind` should be a number")?),
+                "--theme" => theme = Some(value()?),
+                "--seed" => seed = Some(value()?.parse().map_err(|_| "`--seed` should be a whole number")?),
+                "--frames" => {
+                    frames = match value()?.parse() {
+                        Ok(0) | Err(_) => return Err("`--frames` should be a whole number, at least 1"),
+                        Ok(frames) => Some(frames),
+                    }
+                },
+                "--final-frame" => final_frame = Some(value()?),
+                "--fps" => {
+                    fps = match value()?.parse() {
+                        Ok(0) | Err(_) => return Err("`--fps` should be a whole number, at least 1"),
+                        Ok(fps) => Some(fps),
+                    }
+                },
+                "--duration" => duration = Some(parse_duration(&value()?).ok_or("`--duration` should be a time such as `10s`, `1.5m` or `800ms`")?),
+                "--size" => size = Some(parse_size(&value()?).ok_or("`--size` should be written as COLSxROWS, e.g. `80x24`, and be at least 4x4")?),
+                "--color" => {
+                    let setting = value()?;
+                    if ColorDepth::from_setting(&setting).is_err() {
+                        return Err("`--color` should be one of `auto`, `truecolor`, `256`, `16`, `8` or `none`");
+                    }
+
+                    color = Some(setting);
+                },
+                _ => return Err("unrecognized option"),
+            }
+        }
+
+        // a file that happens to be called `config` can still be burned as `./config`
+        let command = match (positional.as_slice(), print) {
+            ([subcommand], true) if subcommand == "config" => Command::PrintConfig,
+            ([subcommand], false) if subcommand == "config" => return Err("`burn config` should be called with `--print`"),
+            ([subcommand, output, filepath], false) if subcommand == "record" => {
+                Command::Record { output: output.clone(), filepath: filepath.clone() }
+            },
+            ([subcommand, output, filepath], false) if subcommand == "export" => {
+                let format = match format.or_else(|| format_from_extension(output)) {
+                    Some(format) => format,
+                    None => return Err("`burn export` should be given `--gif`, `--apng` or `--html`, or an output ending in .gif, .png or .html"),
+                };
+
+                Command::Export { output: output.clone(), filepath: filepath.clone(), format }
+            },
+            ([subcommand, filepath @ ..], false) if subcommand == "sim" && filepath.len() <= 1 => Command::Sim {
+                filepath: filepath.first().cloned(),
+                frames,
+                format: stats_format.unwrap_or(StatsFormat::Json),
+                final_frame: final_frame.clone(),
+            },
+            ([filepath], false) => Command::Burn { filepath: filepath.clone() },
+            _ => return Err(USAGE),
+        };
+
+        if size.is_some() && !matches!(command, Command::Record { .. } | Command::Export { .. } | Command::Sim { .. }) {
+            return Err("`--size` only applies to `burn record`, `burn export` and `burn sim`");
+        }
+        if (frames.is_some() || stats_format.is_some() || final_frame.is_some()) && !matches!(command, Command::Sim { .. }) {
+            return Err("`--frames`, `--json`, `--csv` and `--final-frame` only apply to `burn sim`");
+        }
+        if inline.is_some() && !matches!(command, Command::Burn { .. }) {
+            return Err("`--inline` only applies to `burn`");
+        }
+        if duration.is_some() && !matches!(command, Command::Burn { .. } | Command::Record { .. } | Command::Export { .. }) {
+            return Err("`--duration` only applies to `burn`, `burn record` and `burn export`");
+        }
+        if format.is_some() && !matches!(command, Command::Export { .. }) {
+            return Err("`--gif`, `--apng` and `--html` only apply to `burn export`");
+        }
+
+        Ok(Options { command, translucent_smoke, flicker, delete, smoke_model, wind, color, theme, size, seed, fps, outro, duration, inline })
+    }
+}
+
+fn format_from_extension(output: &str) -> Option<Format> {
+    let extension = output.rsplit('.').next()?.to_lowercase();
+
+    match extension.as_str() {
+        "gif" => Some(Format::Gif),
+        "png" | "apng" => Some(Format::Apng),
+        "html" | "htm" => Some(Format::Html),
+        _ => None,
+    }
+}
+
+/// parse_size reads a terminal size written as `COLSxROWS`. Anything smaller than 4x4 leaves no room inside
+/// the border.
+fn parse_size(size: &str) -> Option<(usize, usize)> {
+    let ix = size.find('x')?;
+    let cols: usize = size[..ix].parse().ok()?;
+    let rows: usize = size[ix + 1..].parse().ok()?;
+
+    if cols < 4 || rows < 4 {
+        None
+    } else {
+        Some((cols, rows))
+    }
+}
+
+/// parse_duration reads a time given in milliseconds, seconds or minutes, e.g. `800ms`, `10s` or `1.5m`. It has to be
+/// more than nothing.
+fn parse_duration(duration: &str) -> Option<Duration> {
+    let (amount, unit_secs) = if let Some(amount) = duration.strip_suffix("ms") {
+        (amount, 0.001)
+    } else if let Some(amount) = duration.strip_suffix('s') {
+        (amount, 1.0)
+    } else if let Some(amount) = duration.strip_suffix('m') {
+        (amount, 60.0)
+    } else {
+        return None;
+    };
+
+    let secs = amount.parse::<f64>().ok()? * unit_secs;
+    if secs.is_finite() && secs > 0.0 && secs < 1e9 {
+        Some(Duration::from_secs_f64(secs))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Command, InlineRows, Options};
+    use std::time::Duration;
+    use burn::config::Config;
+    use burn::export::Format;
+    use burn::outro::Outro;
+    use burn::sim::StatsFormat;
+    use burn::state::SmokeModel;
+
+    fn parse(args: &[&str]) -> Result<Options, &'static str> {
+        Options::parse_from(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let options = parse(&["--smoke", "particles", "notes.txt", "--wind=-0.5", "--color", "16", "--theme=witchfire"]).unwrap();
+
+        assert_eq!(options.command, Command::Burn { filepath: "notes.txt".to_string() });
+        assert_eq!(options.smoke_model, Some(SmokeModel::Particles));
+        assert_eq!(options.wind, Some(-0.5));
+        assert_eq!(options.color, Some("16".to_string()));
+        assert_eq!(options.theme, Some("witchfire".to_string()));
+        assert!(!options.translucent_smoke);
+
+        let options = parse(&["config", "--print", "--flicker"]).unwrap();
+
+        assert_eq!(options.command, Command::PrintConfig);
+        assert!(options.flicker);
+        assert_eq!(options.wind, None);
+
+        let options = parse(&["record", "--size=100x30", "out.cast", "notes.txt"]).unwrap();
+
+        assert_eq!(options.command, Command::Record { output: "out.cast".to_string(), filepath: "notes.txt".to_string() });
+        assert_eq!(options.size, Some((100, 30)));
+
+        let options = parse(&["export", "burn.png", "notes.txt", "--gif"]).unwrap();
+
+        assert_eq!(options.command, Command::Export { output: "burn.png".to_string(), filepath: "notes.txt".to_string(), format: Format::Gif });
+        assert!(matches!(parse(&["export", "burn.apng", "notes.txt"]).unwrap().command, Command::Export { format: Format::Apng, .. }));
+        assert!(matches!(parse(&["export", "--html", "burn", "notes.txt"]).unwrap().command, Command::Export { format: Format::Html, .. }));
+
+        let options = parse(&["sim", "--size", "80x24", "--seed", "1", "--frames=500", "--csv"]).unwrap();
+
+        assert_eq!(options.command, Command::Sim { filepath: None, frames: Some(500), format: StatsFormat::Csv, final_frame: None });
+        assert_eq!(options.seed, Some(1));
+
+        let options = parse(&["notes.txt", "--duration=1.5m", "--fps", "30", "--outro=fade"]).unwrap();
+
+        assert_eq!(options.duration, Some(Duration::from_secs(90)));
+        assert_eq!(options.fps, Some(30));
+        assert_eq!(options.outro, Some(Outro::Fade));
+        assert_eq!(options.inline, None);
+        assert_eq!(parse(&["--inline", "notes.txt"]).unwrap().inline, Some(InlineRows::Fit));
+        assert_eq!(parse(&["--inline=8", "notes.txt"]).unwrap().inline, Some(InlineRows::Exactly(8)));
+        assert_eq!(parse(&["export", "--duration", "800ms", "out.gif", "a.txt"]).unwrap().duration, Some(Duration::from_millis(800)));
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_input() {
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["a.txt", "b.txt"]).is_err());
+        assert!(parse(&["a.txt", "--smoke"]).is_err());
+        assert!(parse(&["a.txt", "--smoke=steam"]).is_err());
+        assert!(parse(&["a.txt", "--unknown"]).is_err());
+        assert!(parse(&["a.txt", "--color=65536"]).is_err());
+        assert!(parse(&["config"]).is_err());
+        assert!(parse(&["a.txt", "--print"]).is_err());
+        assert!(parse(&["a.txt", "--size=80x24"]).is_err());
+        assert!(parse(&["record", "out.cast", "a.txt", "--size=80"]).is_err());
+        assert!(parse(&["record", "out.cast", "a.txt", "--size=2x2"]).is_err());
+        assert!(parse(&["export", "out.webm", "a.txt"]).is_err());
+        assert!(parse(&["a.txt", "--gif"]).is_err());
+        assert!(parse(&["a.txt", "--frames=10"]).is_err());
+        assert!(parse(&["sim", "--frames=0"]).is_err());
+        assert!(parse(&["sim", "--seed=-1"]).is_err());
+        assert!(parse(&["sim", "--duration=10s"]).is_err());
+        assert!(parse(&["a.txt", "--duration=10"]).is_err());
+        assert!(parse(&["a.txt", "--duration=0s"]).is_err());
+        assert!(parse(&["a.txt", "--fps=0"]).is_err());
+        assert!(parse(&["a.txt", "--outro=explode"]).is_err());
+        assert!(parse(&["a.txt", "--inline=2"]).is_err());
+        assert!(parse(&["record", "out.cast", "a.txt", "--inline"]).is_err());
+    }
+
+    #[test]
+    fn test_flags_override_settings() {
+        let mut config = Config::default();
+        config.simulation.wind = -1.0;
+        config.simulation.fire.ttl_max = 40;
+        config.rendering.flicker = true;
+
+        parse(&["a.txt", "--wind=0.5", "--fps", "30"]).unwrap().apply(&mut config);
+
+        assert_eq!(config.simulation.wind, 0.5);
+        assert_eq!(config.rendering.fps, 30);
+        assert_eq!(config.simulation.fire.ttl_max, 40);
+        assert!(config.rendering.flicker);
+    }
+}
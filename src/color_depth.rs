@@ -0,0 +1,293 @@
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use termion::{color, style};
+
+/// The standard 16 ANSI colors, as xterm draws them by default.
+const ANSI_COLORS: &[color::Rgb] = &[
+    color::Rgb(0, 0, 0),
+    color::Rgb(205, 0, 0),
+    color::Rgb(0, 205, 0),
+    color::Rgb(205, 205, 0),
+    color::Rgb(0, 0, 238),
+    color::Rgb(205, 0, 205),
+    color::Rgb(0, 205, 205),
+    color::Rgb(229, 229, 229),
+    color::Rgb(127, 127, 127),
+    color::Rgb(255, 0, 0),
+    color::Rgb(0, 255, 0),
+    color::Rgb(255, 255, 0),
+    color::Rgb(92, 92, 255),
+    color::Rgb(255, 0, 255),
+    color::Rgb(0, 255, 255),
+    color::Rgb(255, 255, 255),
+];
+
+/// Channel levels of the 6x6x6 color cube in the 256-color palette.
+const CUBE_LEVELS: &[u8] = &[0, 95, 135, 175, 215, 255];
+
+/// In monochrome, colors at least this bright are drawn in reverse video, and those at least
+/// `BOLD_LUMINANCE` in bold.
+const REVERSE_LUMINANCE: f64 = 0.8;
+const BOLD_LUMINANCE: f64 = 0.5;
+
+/// Index of the `max_colors` capability among terminfo's numeric capabilities.
+const TERMINFO_MAX_COLORS: usize = 13;
+
+/// How many colors the terminal can show, and so how cell colors should be written out.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ColorDepth {
+    /// 24-bit color.
+    TrueColor,
+    /// The xterm 256-color palette.
+    Ansi256,
+    /// The 8 standard colors plus their bright variants.
+    Ansi16,
+    /// Only the 8 standard colors.
+    Ansi8,
+    /// No color at all; brightness is approximated with bold and reverse video.
+    Monochrome,
+}
+
+impl ColorDepth {
+    /// from_setting reads a `color` setting. `auto` gives `None`, meaning the depth should be detected.
+    pub fn from_setting(setting: &str) -> Result<Option<Self>, String> {
+        match setting {
+            "auto" => Ok(None),
+            "truecolor" => Ok(Some(ColorDepth::TrueColor)),
+            "256" => Ok(Some(ColorDepth::Ansi256)),
+            "16" => Ok(Some(ColorDepth::Ansi16)),
+            "8" => Ok(Some(ColorDepth::Ansi8)),
+            "none" => Ok(Some(ColorDepth::Monochrome)),
+            _ => Err(format!("`{}` is not a color setting; it should be one of `auto`, `truecolor`, `256`, `16`, `8` or `none`", setting)),
+        }
+    }
+
+    /// detect works out the color depth of the terminal from the environment and terminfo. `NO_COLOR`
+    /// always wins, then `COLORTERM`, then `TERM` together with its terminfo entry.
+    pub fn detect() -> Self {
+        let var = |name| env::var(name).ok().filter(|value: &String| !value.is_empty());
+        let term = var("TERM");
+        let max_colors = term.as_ref().and_then(|term| terminfo_max_colors(term));
+
+        Self::detect_from(var("NO_COLOR").is_some(), var("COLORTERM").as_deref(), term.as_deref(), max_colors)
+    }
+
+    fn detect_from(no_color: bool, colorterm: Option<&str>, term: Option<&str>, max_colors: Option<i32>) -> Self {
+        if no_color {
+            return ColorDepth::Monochrome;
+        }
+
+        if let Some("truecolor") | Some("24bit") = colorterm {
+            return ColorDepth::TrueColor;
+        }
+
+        let term = match term {
+            Some("dumb") | None => return ColorDepth::Monochrome,
+            Some(term) => term,
+        };
+
+        if term.ends_with("-direct") || term.contains("truecolor") {
+            return ColorDepth::TrueColor;
+        }
+
+        match max_colors {
+            Some(n) if n >= 1 << 24 => ColorDepth::TrueColor,
+            Some(n) if n >= 256 => ColorDepth::Ansi256,
+            Some(n) if n >= 16 => ColorDepth::Ansi16,
+            Some(n) if n >= 8 => ColorDepth::Ansi8,
+            Some(_) => ColorDepth::Monochrome,
+            // no terminfo entry to go on, so guess from the name
+            None if term.contains("256color") => ColorDepth::Ansi256,
+            None if term.contains("16color") => ColorDepth::Ansi16,
+            None => ColorDepth::Ansi8,
+        }
+    }
+
+    /// paint writes `glyph` in `fg` (or the terminal's default color), approximated as closely as this depth allows.
+    pub fn paint<W: Write>(self, out: &mut W, glyph: char, fg: Option<color::Rgb>) -> io::Result<()> {
+        let fg = match fg {
+            Some(fg) => fg,
+            None => return out.write_all(glyph.encode_utf8(&mut [0; 4]).as_bytes()),
+        };
+
+        match self {
+            ColorDepth::TrueColor => write!(out, "{}{}{}", color::Fg(fg), glyph, color::Fg(color::Reset)),
+            ColorDepth::Ansi256 => write!(out, "{}{}{}", color::Fg(color::AnsiValue(nearest_256(fg))), glyph, color::Fg(color::Reset)),
+            // termion writes even the basic colors in 256-color form, which these terminals may not understand
+            ColorDepth::Ansi16 | ColorDepth::Ansi8 => {
+                let ix = nearest_ansi(fg, if self == ColorDepth::Ansi16 { 16 } else { 8 });
+                let code = if ix < 8 { 30 + ix } else { 90 + ix - 8 };
+
+                write!(out, "\x1b[{}m{}{}", code, glyph, color::Fg(color::Reset))
+            },
+            ColorDepth::Monochrome => {
+                let luminance = luminance(fg);
+
+                if luminance >= REVERSE_LUMINANCE {
+                    write!(out, "{}{}{}", style::Invert, glyph, style::Reset)
+                } else if luminance >= BOLD_LUMINANCE {
+                    write!(out, "{}{}{}", style::Bold, glyph, style::Reset)
+                } else {
+                    write!(out, "{}", glyph)
+                }
+            },
+        }
+    }
+}
+
+fn distance(a: color::Rgb, b: color::Rgb) -> u32 {
+    let channel = |x: u8, y: u8| (i32::from(x) - i32::from(y)).pow(2) as u32;
+
+    channel(a.0, b.0) + channel(a.1, b.1) + channel(a.2, b.2)
+}
+
+/// luminance approximates perceived brightness in `0.0..=1.0`.
+fn luminance(rgb: color::Rgb) -> f64 {
+    (0.2126 * f64::from(rgb.0) + 0.7152 * f64::from(rgb.1) + 0.0722 * f64::from(rgb.2)) / 255.0
+}
+
+/// nearest_ansi finds the closest of the first `n` ANSI colors to `rgb`.
+fn nearest_ansi(rgb: color::Rgb, n: usize) -> usize {
+    (0..n).min_by_key(|&ix| distance(rgb, ANSI_COLORS[ix])).unwrap()
+}
+
+/// nearest_256 finds the closest color to `rgb` in the 256-color palette's color cube or gray ramp.
+fn nearest_256(rgb: color::Rgb) -> u8 {
+    let nearest_level = |channel: u8| {
+        (0..CUBE_LEVELS.len()).min_by_key(|&ix| (i32::from(CUBE_LEVELS[ix]) - i32::from(channel)).abs()).unwrap()
+    };
+
+    let (r, g, b) = (nearest_level(rgb.0), nearest_level(rgb.1), nearest_level(rgb.2));
+    let cube = color::Rgb(CUBE_LEVELS[r], CUBE_LEVELS[g], CUBE_LEVELS[b]);
+
+    // the gray ramp runs from 8 to 238 in steps of 10
+    let average = (u32::from(rgb.0) + u32::from(rgb.1) + u32::from(rgb.2)) / 3;
+    let gray_ix = (average.saturating_sub(3) / 10).min(23) as u8;
+    let gray_level = 8 + 10 * gray_ix;
+    let gray = color::Rgb(gray_level, gray_level, gray_level);
+
+    if distance(rgb, gray) < distance(rgb, cube) {
+        232 + gray_ix
+    } else {
+        16 + 36 * r as u8 + 6 * g as u8 + b as u8
+    }
+}
+
+/// terminfo_max_colors looks up the `colors` capability of `term` in the terminfo database.
+fn terminfo_max_colors(term: &str) -> Option<i32> {
+    let first = term.chars().next()?;
+
+    let mut dirs: Vec<PathBuf> = vec![];
+    if let Some(dir) = env::var_os("TERMINFO") {
+        dirs.push(dir.into());
+    }
+    if let Some(home) = env::var_os("HOME") {
+        dirs.push(PathBuf::from(home).join(".terminfo"));
+    }
+    if let Ok(extra) = env::var("TERMINFO_DIRS") {
+        dirs.extend(extra.split(':').filter(|dir| !dir.is_empty()).map(PathBuf::from));
+    }
+    dirs.extend(["/etc/terminfo", "/lib/terminfo", "/usr/share/terminfo"].iter().map(PathBuf::from));
+
+    dirs.iter()
+        .flat_map(|dir| {
+            // entries live under their first letter, or its hex code on some systems
+            vec![dir.join(first.to_string()).join(term), dir.join(format!("{:x}", first as u32)).join(term)]
+        })
+        .filter_map(|path| fs::read(path).ok())
+        .filter_map(|entry| parse_max_colors(&entry))
+        .next()
+}
+
+/// parse_max_colors reads the `colors` capability out of a compiled terminfo entry.
+fn parse_max_colors(entry: &[u8]) -> Option<i32> {
+    let header = |ix: usize| -> Option<usize> {
+        let bytes = entry.get(2 * ix..2 * ix + 2)?;
+        Some(u16::from(bytes[0]) as usize | (u16::from(bytes[1]) as usize) << 8)
+    };
+
+    let number_size = match header(0)? {
+        0o432 => 2,
+        0o1036 => 4,
+        _ => return None,
+    };
+    let (names_size, bools_count, numbers_count) = (header(1)?, header(2)?, header(3)?);
+
+    if TERMINFO_MAX_COLORS >= numbers_count {
+        return None;
+    }
+
+    // numbers are aligned to an even offset
+    let numbers_start = 12 + names_size + bools_count;
+    let numbers_start = numbers_start + numbers_start % 2;
+    let offset = numbers_start + TERMINFO_MAX_COLORS * number_size;
+    let bytes = entry.get(offset..offset + number_size)?;
+
+    let value = if number_size == 2 {
+        i32::from(i16::from(bytes[0]) | i16::from(bytes[1]) << 8)
+    } else {
+        i32::from(bytes[0]) | i32::from(bytes[1]) << 8 | i32::from(bytes[2]) << 16 | i32::from(bytes[3]) << 24
+    };
+
+    // absent capabilities are stored as negative numbers
+    if value < 0 { None } else { Some(value) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{nearest_256, nearest_ansi, parse_max_colors, ColorDepth};
+    use termion::color::Rgb;
+
+    #[test]
+    fn test_detect_precedence() {
+        assert_eq!(ColorDepth::detect_from(true, Some("truecolor"), Some("xterm-256color"), Some(256)), ColorDepth::Monochrome);
+        assert_eq!(ColorDepth::detect_from(false, Some("truecolor"), Some("screen"), Some(8)), ColorDepth::TrueColor);
+        assert_eq!(ColorDepth::detect_from(false, None, Some("screen"), Some(8)), ColorDepth::Ansi8);
+        assert_eq!(ColorDepth::detect_from(false, None, Some("xterm-256color"), None), ColorDepth::Ansi256);
+        assert_eq!(ColorDepth::detect_from(false, None, Some("xterm-direct"), Some(256)), ColorDepth::TrueColor);
+        assert_eq!(ColorDepth::detect_from(false, None, Some("dumb"), None), ColorDepth::Monochrome);
+        assert_eq!(ColorDepth::detect_from(false, None, None, None), ColorDepth::Monochrome);
+    }
+
+    #[test]
+    fn test_quantization() {
+        assert_eq!(nearest_256(Rgb(255, 0, 0)), 196);
+        assert_eq!(nearest_256(Rgb(128, 128, 128)), 244);
+        assert_eq!(nearest_ansi(Rgb(250, 10, 10), 16), 9);
+        assert_eq!(nearest_ansi(Rgb(250, 10, 10), 8), 1);
+    }
+
+    #[test]
+    fn test_paint_escapes() {
+        let paint = |depth: ColorDepth, rgb| {
+            let mut out = vec![];
+            depth.paint(&mut out, '^', Some(rgb)).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        assert_eq!(paint(ColorDepth::TrueColor, Rgb(1, 2, 3)), "\x1b[38;2;1;2;3m^\x1b[39m");
+        assert_eq!(paint(ColorDepth::Ansi256, Rgb(255, 0, 0)), "\x1b[38;5;196m^\x1b[39m");
+        assert_eq!(paint(ColorDepth::Ansi16, Rgb(255, 255, 0)), "\x1b[93m^\x1b[39m");
+        assert_eq!(paint(ColorDepth::Monochrome, Rgb(255, 255, 255)), "\x1b[7m^\x1b[m");
+        assert_eq!(paint(ColorDepth::Monochrome, Rgb(20, 20, 20)), "^");
+    }
+
+    #[test]
+    fn test_parse_terminfo() {
+        // legacy format: header, "x\0" for names, one boolean, then 14 numbers with colors#256 last
+        let mut entry: Vec<u8> = vec![0x1a, 0x01, 2, 0, 1, 0, 14, 0, 0, 0, 0, 0];
+        entry.extend_from_slice(b"x\0");
+        entry.push(1);
+        entry.push(0);
+        for _ in 0..13 {
+            entry.extend_from_slice(&[0xff, 0xff]);
+        }
+        entry.extend_from_slice(&[0, 1]);
+
+        assert_eq!(parse_max_colors(&entry), Some(256));
+        assert_eq!(parse_max_colors(&entry[..entry.len() - 1]), None);
+    }
+}
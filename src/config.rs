@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use toml;
+
+use color_depth::ColorDepth;
+use outro::Outro;
+use state::SimulationConfig;
+use theme;
+
+/// Names a config file to read instead of `config.toml` in the config directory.
+const CONFIG_PATH_VAR: &str = "BURN_CONFIG";
+
+/// Env vars named after a setting's path under this prefix override it, e.g. `BURN_RENDERING_THEME` for
+/// `rendering.theme` or `BURN_SIMULATION_FIRE_TTL_MAX` for `simulation.fire.ttl_max`.
+const ENV_PREFIX: &str = "BURN";
+
+/// How the burn is drawn.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RenderingConfig {
+    /// Length of a simulation tick, in milliseconds.
+    pub tick_ms: u64,
+    /// The most frames drawn a second. Ticks that come faster than this are simulated without being drawn.
+    pub fps: u32,
+    /// A theme name or path to a theme file.
+    pub theme: String,
+    /// One of `auto`, `truecolor`, `256`, `16`, `8` or `none`.
+    pub color: String,
+    /// Flames flicker in patches of neighboring cells rather than all burning steadily.
+    pub flicker: bool,
+    /// Sparse smoke dims the layers beneath it instead of hiding them.
+    pub translucent_smoke: bool,
+    /// What happens once the burn is over: `none`, `hold`, `fade` or `summary`.
+    pub outro: Outro,
+    /// How long the outro lasts, in milliseconds.
+    pub outro_ms: u64,
+    /// The longest a burn plays for, in milliseconds, before it's cut short and the outro plays, however short its
+    /// ticks are.
+    pub max_burn_ms: u64,
+}
+
+impl Default for RenderingConfig {
+    fn default() -> Self {
+        RenderingConfig {
+            tick_ms: 100,
+            fps: 60,
+            theme: theme::DEFAULT_THEME.to_string(),
+            color: "auto".to_string(),
+            flicker: false,
+            translucent_smoke: false,
+            outro: Outro::Hold,
+            outro_ms: 2000,
+            max_burn_ms: 600_000,
+        }
+    }
+}
+
+/// Every setting `burn` reads. Each layer is optional and overrides the ones before it: the built-in defaults,
+/// then the config file, then `BURN_*` env vars, then command-line flags. Deleting the burned file isn't among them:
+/// it has to be asked for on each run, with `--delete`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub simulation: SimulationConfig,
+    pub rendering: RenderingConfig,
+}
+
+impl Config {
+    /// load reads the config file, if there is one, and layers the process's env vars over it.
+    pub fn load() -> Result<Self, String> {
+        let path = match env::var_os(CONFIG_PATH_VAR) {
+            Some(path) => Some(PathBuf::from(path)),
+            None => config_dir().map(|dir| dir.join("config.toml")).filter(|path| path.is_file()),
+        };
+
+        let file = match path {
+            Some(path) => {
+                let source = fs::read_to_string(&path).map_err(|err| format!("could not read config {}: {}", path.display(), err))?;
+                toml::from_str(&source).map_err(|err| format!("invalid config {}: {}", path.display(), err))?
+            },
+            None => Config::default(),
+        };
+
+        file.with_vars(env::vars())
+    }
+
+    /// with_vars overrides settings with any `BURN_*` variables among `vars`. Each value is read according to the
+    /// type of the setting it replaces. Variables that don't name a setting are left alone, since other tools may use
+    /// names that start the same way.
+    fn with_vars<I: IntoIterator<Item = (String, String)>>(&self, vars: I) -> Result<Self, String> {
+        let vars: HashMap<String, String> = vars.into_iter().filter(|(name, _)| name.starts_with(ENV_PREFIX)).collect();
+        let mut settings = toml::Value::try_from(self).expect("settings should always serialize");
+
+        override_from_vars(&mut settings, ENV_PREFIX, &vars)?;
+
+        settings.try_into().map_err(|err| format!("invalid setting in the environment: {}", err))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        self.simulation.validate()?;
+        if self.rendering.tick_ms == 0 {
+            return Err("[rendering] `tick_ms` should be at least 1".to_string());
+        }
+        if self.rendering.fps == 0 {
+            return Err("[rendering] `fps` should be at least 1".to_string());
+        }
+        if self.rendering.max_burn_ms == 0 {
+            return Err("[rendering] `max_burn_ms` should be at least 1".to_string());
+        }
+        self.color_depth().map(|_| ())
+    }
+
+    /// color_depth reads the `color` setting; `None` means it should be detected from the terminal.
+    pub fn color_depth(&self) -> Result<Option<ColorDepth>, String> {
+        ColorDepth::from_setting(&self.rendering.color).map_err(|err| format!("[rendering] {}", err))
+    }
+
+    /// to_toml writes out every setting in the config file's format.
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("settings should always serialize")
+    }
+}
+
+/// override_from_vars replaces `setting`, called `name`, and everything under it with the variables named after them.
+fn override_from_vars(setting: &mut toml::Value, name: &str, vars: &HashMap<String, String>) -> Result<(), String> {
+    if let toml::Value::Table(ref mut table) = *setting {
+        for (key, value) in table.iter_mut() {
+            override_from_vars(value, &format!("{}_{}", name, key.to_uppercase()), vars)?;
+        }
+
+        return Ok(());
+    }
+
+    if let Some(raw) = vars.get(name) {
+        let parsed = match *setting {
+            toml::Value::String(_) => Some(toml::Value::String(raw.clone())),
+            toml::Value::Integer(_) => raw.parse().ok().map(toml::Value::Integer),
+            toml::Value::Float(_) => raw.parse().ok().map(toml::Value::Float),
+            toml::Value::Boolean(_) => raw.parse().ok().map(toml::Value::Boolean),
+            _ => None,
+        };
+
+        *setting = parsed.ok_or_else(|| format!("`{}` should be a valid {}", name, setting.type_str()))?;
+    }
+
+    Ok(())
+}
+
+/// config_dir is `burn`'s configuration directory, `$XDG_CONFIG_HOME/burn` or `~/.config/burn`.
+pub fn config_dir() -> Option<PathBuf> {
+    match env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => Some(PathBuf::from(dir).join("burn")),
+        None => env::var_os("HOME").map(|home| PathBuf::from(home).join(".config").join("burn")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+    use state::{FluidSmokeConfig, SmokeModel};
+    use toml;
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|&(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn test_layering_precedence() {
+        let file: Config = toml::from_str("
+            [simulation]
+            wind = 0.25
+            [simulation.fire]
+            ttl_max = 40
+            [rendering]
+            theme = \"witchfire\"
+            tick_ms = 50
+        ").unwrap();
+
+        let config = file.with_vars(vars(&[
+            ("BURN_RENDERING_THEME", "chemical"),
+            ("BURN_SIMULATION_WIND", "-1"),
+            ("BURN_SIMULATION_SMOKE_MODEL", "particles"),
+            ("HOME", "/nowhere"),
+        ])).unwrap();
+
+        assert_eq!(config.simulation.fire.ttl_max, 40);
+        assert_eq!(config.simulation.fire.ttl_min, 3);
+        assert_eq!(config.rendering.tick_ms, 50);
+        assert_eq!(config.rendering.theme, "chemical");
+        assert_eq!(config.simulation.smoke_model, SmokeModel::Particles);
+        assert_eq!(config.simulation.wind, -1.0);
+
+        let printed: Config = toml::from_str(&config.to_toml()).unwrap();
+        assert_eq!(printed.rendering.theme, "chemical");
+    }
+
+    #[test]
+    fn test_rejects_bad_settings() {
+        let defaults = Config::default();
+
+        assert!(defaults.with_vars(vars(&[("BURN_RENDERING_TICK_MS", "soon")])).is_err());
+        assert!(defaults.with_vars(vars(&[("BURN_SIMULATION_SMOKE_MODEL", "steam")])).is_err());
+        assert!(toml::from_str::<Config>("[rendering]\nspeed = 2").is_err());
+        assert!(toml::from_str::<Config>("[rendering]\nframe_ms = 50").is_err());
+
+        // other tools' variables that happen to share the prefix are none of our business
+        let config = defaults.with_vars(vars(&[("BURN_FOO", "1"), ("BURN_CONFIG", "burn.toml"), ("BURNT", "toast")])).unwrap();
+        assert_eq!(config.rendering.tick_ms, defaults.rendering.tick_ms);
+
+        let mut config = Config::default();
+        config.simulation.fire.ttl_min = 30;
+        assert!(config.validate().is_err());
+
+        let mut config = Config::default();
+        config.rendering.color = "65536".to_string();
+        assert!(config.validate().is_err());
+
+        let mut config = Config::default();
+        config.rendering.fps = 0;
+        assert!(config.validate().is_err());
+
+        let mut config = Config::default();
+        config.rendering.max_burn_ms = 0;
+        assert!(config.validate().is_err());
+
+        for &wind in &[f64::NAN, f64::INFINITY] {
+            let mut config = Config::default();
+            config.simulation.wind = wind;
+            assert!(config.validate().is_err());
+        }
+
+        let mut config = Config::default();
+        config.simulation.ash.crumble_odds = 0;
+        assert_eq!(config.validate().unwrap_err(), "[simulation.ash] crumble_odds should be at least 1");
+
+        for &(diffusion, decay, source) in &[(1.5, 0.02, 0.12), (0.15, -0.1, 0.12), (0.15, 0.02, -1.0), (0.15, 0.02, f64::NAN)] {
+            let mut config = Config::default();
+            config.simulation.fluid = FluidSmokeConfig { diffusion, decay, source, ..FluidSmokeConfig::default() };
+            assert!(config.validate().is_err());
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use std::io::{self, Write};
+
+use gif;
+
+use super::{Encoder, Frame, Raster, Rect};
+
+/// How hard the quantizer works on frames with more than 256 colors, from 1 (best) to 30 (fastest).
+const QUANTIZER_SPEED: i32 = 10;
+
+/// Most viewers play anything shorter than this (in hundredths of a second) much more slowly than asked.
+const MIN_DELAY: u16 = 2;
+
+fn to_io_error(err: gif::EncodingError) -> io::Error {
+    io::Error::other(err)
+}
+
+/// Writes a looping GIF. Each frame after the first only covers the region that changed, with unchanged pixels left
+/// transparent, and gets its own palette.
+pub struct GifEncoder<W: Write> {
+    encoder: gif::Encoder<W>,
+    previous: Option<Raster>,
+}
+
+impl<W: Write> GifEncoder<W> {
+    /// new starts a GIF `width` by `height` pixels on `out`. Neither can be more than `u16::MAX`.
+    pub fn new(out: W, width: usize, height: usize) -> io::Result<Self> {
+        let mut encoder = gif::Encoder::new(out, width as u16, height as u16, &[]).map_err(to_io_error)?;
+        encoder.set_repeat(gif::Repeat::Infinite).map_err(to_io_error)?;
+
+        Ok(GifEncoder { encoder, previous: None })
+    }
+}
+
+impl<W: Write> Encoder for GifEncoder<W> {
+    fn frame(&mut self, frame: &Frame, changed: Rect, ms: u64) -> io::Result<()> {
+        let raster = Raster::rasterize(frame);
+        let region = changed.in_pixels();
+        let mut rgba = raster.crop(region, self.previous.as_ref());
+
+        let mut frame = gif::Frame::from_rgba_speed(region.width as u16, region.height as u16, &mut rgba, QUANTIZER_SPEED);
+        frame.left = region.x as u16;
+        frame.top = region.y as u16;
+        frame.delay = delay(ms);
+        frame.dispose = gif::DisposalMethod::Keep;
+
+        self.encoder.write_frame(&frame).map_err(to_io_error)?;
+        self.previous = Some(raster);
+
+        Ok(())
+    }
+
+    fn finish(self) -> io::Result<()> {
+        self.encoder.into_inner()?.flush()
+    }
+}
+
+/// delay is how long a frame shown for `ms` milliseconds lasts, in hundredths of a second. Pauses longer than a GIF
+/// can hold are cut down to the longest it can.
+fn delay(ms: u64) -> u16 {
+    (ms / 10).clamp(u64::from(MIN_DELAY), u64::from(u16::MAX)) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{delay, MIN_DELAY};
+
+    #[test]
+    fn test_delay() {
+        assert_eq!(delay(300), 30);
+        assert_eq!(delay(5), MIN_DELAY);
+        assert_eq!(delay(1_000_000), u16::MAX);
+    }
+}
@@ -0,0 +1,159 @@
+use std::io::{self, Write};
+
+use png;
+use png::chunk::{self, ChunkType};
+
+use super::{Encoder, Frame, Raster, Rect};
+
+/// Where the chunks of a PNG file start, after its signature.
+const SIGNATURE_LEN: usize = 8;
+
+fn to_io_error(err: png::EncodingError) -> io::Error {
+    io::Error::other(err)
+}
+
+/// One frame of the animation, compressed and waiting for the rest.
+struct Encoded {
+    /// The part of the canvas the frame covers, in pixels.
+    region: Rect,
+    ms: u64,
+    /// The frame's image data, as it goes in an `IDAT` or `fdAT` chunk.
+    data: Vec<u8>,
+}
+
+/// Writes a looping APNG. Each frame after the first only covers the region that changed, and is blended over the
+/// frame before so that unchanged pixels can be left transparent.
+///
+/// An APNG has to say how many frames it has before the first one, so nothing is written until the last frame is in.
+/// Until then, each frame is kept compressed, as it will be written.
+pub struct ApngEncoder<W: Write> {
+    out: W,
+    width: usize,
+    height: usize,
+    previous: Option<Raster>,
+    frames: Vec<Encoded>,
+}
+
+impl<W: Write> ApngEncoder<W> {
+    /// new starts an APNG `width` by `height` pixels, to be written to `out`.
+    pub fn new(out: W, width: usize, height: usize) -> Self {
+        ApngEncoder { out, width, height, previous: None, frames: vec![] }
+    }
+}
+
+/// png_encoder sets up a PNG `width` by `height` pixels in the colors the animation uses.
+fn png_encoder<W: Write>(out: W, width: usize, height: usize) -> png::Encoder<'static, W> {
+    let mut encoder = png::Encoder::new(out, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_compression(png::Compression::Best);
+
+    encoder
+}
+
+/// compress encodes `rgba`, an image `width` by `height` pixels, as a PNG of its own and takes out the image data.
+fn compress(rgba: &[u8], width: usize, height: usize) -> io::Result<Vec<u8>> {
+    let mut png = vec![];
+    let mut writer = png_encoder(&mut png, width, height).write_header().map_err(to_io_error)?;
+    writer.write_image_data(rgba).map_err(to_io_error)?;
+    writer.finish().map_err(to_io_error)?;
+
+    // each chunk is its length, type, data and a checksum
+    let mut data = vec![];
+    let mut rest = &png[SIGNATURE_LEN..];
+
+    while rest.len() >= 12 {
+        let len = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        if &rest[4..8] == b"IDAT" {
+            data.extend_from_slice(&rest[8..8 + len]);
+        }
+        rest = &rest[12 + len..];
+    }
+
+    Ok(data)
+}
+
+impl<W: Write> Encoder for ApngEncoder<W> {
+    fn frame(&mut self, frame: &Frame, changed: Rect, ms: u64) -> io::Result<()> {
+        let raster = Raster::rasterize(frame);
+        let region = changed.in_pixels();
+        let data = compress(&raster.crop(region, self.previous.as_ref()), region.width, region.height)?;
+
+        self.frames.push(Encoded { region, ms, data });
+        self.previous = Some(raster);
+
+        Ok(())
+    }
+
+    fn finish(self) -> io::Result<()> {
+        let mut writer = png_encoder(self.out, self.width, self.height).write_header().map_err(to_io_error)?;
+        let mut write_chunk = |name: ChunkType, parts: &[&[u8]]| writer.write_chunk(name, &parts.concat()).map_err(to_io_error);
+
+        // loops forever
+        write_chunk(chunk::acTL, &[&(self.frames.len() as u32).to_be_bytes(), &0u32.to_be_bytes()])?;
+
+        let mut sequence = 0u32;
+        let mut next_sequence = || {
+            sequence += 1;
+            (sequence - 1).to_be_bytes()
+        };
+
+        for (ix, frame) in self.frames.iter().enumerate() {
+            let Rect { x, y, width, height } = frame.region;
+            let delay_ms = frame.ms.min(u64::from(u16::MAX)) as u16;
+            // the first frame is the default image, which covers the whole canvas; the rest are blended over it
+            let blend_op = if ix == 0 { png::BlendOp::Source } else { png::BlendOp::Over };
+
+            write_chunk(chunk::fcTL, &[
+                &next_sequence(),
+                &(width as u32).to_be_bytes(),
+                &(height as u32).to_be_bytes(),
+                &(x as u32).to_be_bytes(),
+                &(y as u32).to_be_bytes(),
+                &delay_ms.to_be_bytes(),
+                &1000u16.to_be_bytes(),
+                &[png::DisposeOp::None as u8, blend_op as u8],
+            ])?;
+
+            if ix == 0 {
+                write_chunk(chunk::IDAT, &[&frame.data])?;
+            } else {
+                write_chunk(chunk::fdAT, &[&next_sequence(), &frame.data])?;
+            }
+        }
+
+        writer.finish().map_err(to_io_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{export, Format};
+    use config::Config;
+    use pace::Pace;
+    use png;
+    use scene::Scene;
+    use std::time::Duration;
+
+    #[test]
+    fn test_decodes_as_an_animation() {
+        let config = Config::default();
+        let mut scene = Scene::from_text("ashes", 6, 10, &config, 5).unwrap();
+        let mut apng = vec![];
+
+        export(&mut scene, 6, 10, Pace::every(Duration::from_millis(40)), Format::Apng, &mut apng).unwrap();
+
+        let mut reader = png::Decoder::new(&apng[..]).read_info().unwrap();
+        let frames = reader.info().animation_control.unwrap().num_frames;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let mut total_ms = 0;
+
+        for _ in 0..frames {
+            reader.next_frame(&mut buf).unwrap();
+            total_ms += u64::from(reader.info().frame_control.unwrap().delay_num);
+        }
+
+        assert!(frames > 1);
+        assert_eq!(total_ms, 40 * (scene.ticks() + 1));
+    }
+}
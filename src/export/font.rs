@@ -0,0 +1,182 @@
+/// Glyphs for printable ASCII, 16 to a row, one bit per pixel. This is the 8x13 font from the X.org misc-fixed
+/// collection, which is in the public domain.
+const ASCII_GLYPHS: &[u8] = include_bytes!("../../fonts/8x13.raw");
+
+pub const GLYPH_WIDTH: usize = 8;
+pub const GLYPH_HEIGHT: usize = 13;
+
+/// Where box-drawing lines cross the middle of a cell.
+const CENTER_X: usize = 3;
+const CENTER_Y: usize = 6;
+
+/// How far each line of a double box-drawing stroke sits from the middle of the cell.
+const DOUBLE_OFFSET: isize = 2;
+
+#[derive(Copy, Clone, PartialEq)]
+enum Stroke {
+    None,
+    Light,
+    Heavy,
+    Double,
+}
+
+/// The strokes leaving the middle of a box-drawing glyph, as (left, right, up, down).
+fn box_strokes(glyph: char) -> Option<(Stroke, Stroke, Stroke, Stroke)> {
+    use self::Stroke::{Double, Heavy, Light, None};
+
+    let strokes = match glyph {
+        '─' => (Light, Light, None, None),
+        '│' => (None, None, Light, Light),
+        '┌' | '╭' => (None, Light, None, Light),
+        '┐' | '╮' => (Light, None, None, Light),
+        '└' | '╰' => (None, Light, Light, None),
+        '┘' | '╯' => (Light, None, Light, None),
+        '├' => (None, Light, Light, Light),
+        '┤' => (Light, None, Light, Light),
+        '┬' => (Light, Light, None, Light),
+        '┴' => (Light, Light, Light, None),
+        '┼' => (Light, Light, Light, Light),
+        '━' => (Heavy, Heavy, None, None),
+        '┃' => (None, None, Heavy, Heavy),
+        '┏' => (None, Heavy, None, Heavy),
+        '┓' => (Heavy, None, None, Heavy),
+        '┗' => (None, Heavy, Heavy, None),
+        '┛' => (Heavy, None, Heavy, None),
+        '═' => (Double, Double, None, None),
+        '║' => (None, None, Double, Double),
+        '╔' => (None, Double, None, Double),
+        '╗' => (Double, None, None, Double),
+        '╚' => (None, Double, Double, None),
+        '╝' => (Double, None, Double, None),
+        _ => return Option::None,
+    };
+
+    Some(strokes)
+}
+
+/// Fraction of pixels lit by the shade and block glyphs.
+fn shade(glyph: char) -> Option<u8> {
+    match glyph {
+        '░' => Some(1),
+        '▒' => Some(2),
+        '▓' => Some(3),
+        '█' => Some(4),
+        _ => None,
+    }
+}
+
+/// A glyph's pixels, one `bool` per pixel in rows of `GLYPH_WIDTH`.
+pub struct Bitmap([bool; GLYPH_WIDTH * GLYPH_HEIGHT]);
+
+impl Bitmap {
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.0[y * GLYPH_WIDTH + x]
+    }
+
+    fn set(&mut self, x: isize, y: isize) {
+        if x >= 0 && y >= 0 && (x as usize) < GLYPH_WIDTH && (y as usize) < GLYPH_HEIGHT {
+            self.0[y as usize * GLYPH_WIDTH + x as usize] = true;
+        }
+    }
+
+    fn hline(&mut self, y: isize, from_x: isize, to_x: isize) {
+        for x in from_x.min(to_x)..=from_x.max(to_x) {
+            self.set(x, y);
+        }
+    }
+
+    fn vline(&mut self, x: isize, from_y: isize, to_y: isize) {
+        for y in from_y.min(to_y)..=from_y.max(to_y) {
+            self.set(x, y);
+        }
+    }
+}
+
+/// bitmap draws `glyph`. Characters the font doesn't cover are drawn as `?`.
+pub fn bitmap(glyph: char) -> Bitmap {
+    let mut bitmap = Bitmap([false; GLYPH_WIDTH * GLYPH_HEIGHT]);
+
+    if let Some(strokes) = box_strokes(glyph) {
+        draw_box(&mut bitmap, strokes);
+    } else if let Some(quarters) = shade(glyph) {
+        for y in 0..GLYPH_HEIGHT {
+            for x in 0..GLYPH_WIDTH {
+                let lit = match quarters {
+                    1 => x % 2 == 0 && y % 2 == 0,
+                    2 => (x + y) % 2 == 0,
+                    3 => !(x % 2 == 1 && y % 2 == 1),
+                    _ => true,
+                };
+
+                if lit {
+                    bitmap.set(x as isize, y as isize);
+                }
+            }
+        }
+    } else {
+        let index = match glyph {
+            ' '..='~' => glyph as usize - ' ' as usize,
+            _ => '?' as usize - ' ' as usize,
+        };
+        let (left, top) = ((index % 16) * GLYPH_WIDTH, (index / 16) * GLYPH_HEIGHT);
+        let bytes_per_row = 16 * GLYPH_WIDTH / 8;
+
+        for y in 0..GLYPH_HEIGHT {
+            for x in 0..GLYPH_WIDTH {
+                let bit = left + x;
+                let byte = ASCII_GLYPHS[(top + y) * bytes_per_row + bit / 8];
+
+                if byte >> (7 - bit % 8) & 1 == 1 {
+                    bitmap.set(x as isize, y as isize);
+                }
+            }
+        }
+    }
+
+    bitmap
+}
+
+fn draw_box(bitmap: &mut Bitmap, (left, right, up, down): (Stroke, Stroke, Stroke, Stroke)) {
+    let (cx, cy) = (CENTER_X as isize, CENTER_Y as isize);
+    let (max_x, max_y) = (GLYPH_WIDTH as isize - 1, GLYPH_HEIGHT as isize - 1);
+
+    // a double corner is two nested corners, so each of its lines stops short or runs long depending on which side
+    // of the bend it's on
+    let horizontal = if left != Stroke::None { -1 } else { 1 };
+    let vertical = if up != Stroke::None { -1 } else { 1 };
+    let is_corner = (left == Stroke::None) != (right == Stroke::None) && (up == Stroke::None) != (down == Stroke::None);
+
+    for &(stroke, to_x) in &[(left, 0), (right, max_x)] {
+        match stroke {
+            Stroke::None => {},
+            Stroke::Light => bitmap.hline(cy, cx, to_x),
+            Stroke::Heavy => {
+                bitmap.hline(cy, cx, to_x);
+                bitmap.hline(cy + 1, cx, to_x);
+            },
+            Stroke::Double => {
+                for &dy in &[-DOUBLE_OFFSET, DOUBLE_OFFSET] {
+                    let from_x = if is_corner { cx + horizontal * dy * vertical } else { cx };
+                    bitmap.hline(cy + dy, from_x, to_x);
+                }
+            },
+        }
+    }
+
+    for &(stroke, to_y) in &[(up, 0), (down, max_y)] {
+        match stroke {
+            Stroke::None => {},
+            Stroke::Light => bitmap.vline(cx, cy, to_y),
+            Stroke::Heavy => {
+                bitmap.vline(cx, cy, to_y);
+                bitmap.vline(cx + 1, cy, to_y);
+            },
+            Stroke::Double => {
+                for &dx in &[-DOUBLE_OFFSET, DOUBLE_OFFSET] {
+                    let from_y = if is_corner { cy + vertical * dx * horizontal } else { cy };
+                    bitmap.vline(cx + dx, from_y, to_y);
+                }
+            },
+        }
+    }
+}
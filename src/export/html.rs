@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use json;
+
+use super::{Encoder, Frame, Rect, BACKGROUND, DEFAULT_FG};
+
+/// Palette indices are written in base 64 with these digits, `COLOR_DIGITS` to a cell.
+const DIGITS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Played back by the page: draws a grid of one span per cell, then applies each frame's runs in turn and loops.
+const PLAYER: &str = r#"(function () {
+  var burn = document.getElementById("burn");
+  var cells = [];
+
+  for (var i = 0; i < ROWS; i++) {
+    for (var j = 0; j < COLS; j++) {
+      cells.push(burn.appendChild(document.createElement("span")));
+    }
+    burn.appendChild(document.createTextNode("\n"));
+  }
+
+  var next = 0;
+  function show() {
+    var frame = FRAMES[next];
+    next = (next + 1) % FRAMES.length;
+
+    frame[1].forEach(function (run) {
+      var glyphs = Array.from(run[2]);
+      for (var k = 0; k < glyphs.length; k++) {
+        var color = 0;
+        for (var d = 0; d < COLOR_DIGITS; d++) {
+          color = color * 64 + DIGITS.indexOf(run[3][k * COLOR_DIGITS + d]);
+        }
+
+        var cell = cells[run[0] * COLS + run[1] + k];
+        cell.textContent = glyphs[k];
+        cell.style.color = PALETTE[color];
+      }
+    });
+
+    setTimeout(show, frame[0]);
+  }
+
+  show();
+})();"#;
+
+/// A stretch of neighboring cells on one row that changed, as (row, col, glyphs, palette index of each glyph).
+type Run = (usize, usize, String, Vec<usize>);
+
+fn hex([r, g, b]: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// runs finds what changed between `before` and `after`, or everything in `after` if there is no frame before it.
+fn runs(before: Option<&Frame>, after: &Frame, palette: &mut Vec<String>, palette_ix: &mut HashMap<String, usize>) -> Vec<Run> {
+    let mut runs: Vec<Run> = vec![];
+
+    for (i, row) in after.iter_rows().enumerate() {
+        let mut last_col = None;
+
+        for (j, cell) in row.iter().enumerate() {
+            if before.is_some_and(|before| before[(i, j)] == *cell) {
+                continue;
+            }
+
+            let color = hex(cell.fg.map_or(DEFAULT_FG, |rgb| [rgb.0, rgb.1, rgb.2]));
+            let color_ix = *palette_ix.entry(color.clone()).or_insert_with(|| {
+                palette.push(color);
+                palette.len() - 1
+            });
+
+            match runs.last_mut() {
+                Some(run) if last_col.map(|col| col + 1) == Some(j) && run.0 == i => {
+                    run.2.push(cell.glyph);
+                    run.3.push(color_ix);
+                },
+                _ => runs.push((i, j, cell.glyph.to_string(), vec![color_ix])),
+            }
+
+            last_col = Some(j);
+        }
+    }
+
+    runs
+}
+
+/// color_digits is how many base 64 digits it takes to write any index into a palette of `colors` colors.
+fn color_digits(colors: usize) -> usize {
+    let mut digits = 1;
+    while 64usize.pow(digits as u32) < colors {
+        digits += 1;
+    }
+
+    digits
+}
+
+fn encode_colors(colors: &[usize], digits: usize) -> String {
+    let alphabet = DIGITS.as_bytes();
+    let mut encoded = String::with_capacity(colors.len() * digits);
+
+    for &color in colors {
+        for d in (0..digits).rev() {
+            encoded.push(alphabet[color / 64usize.pow(d as u32) % 64] as char);
+        }
+    }
+
+    encoded
+}
+
+/// Writes a self-contained HTML page that plays the frames back on a loop. Only the cells that change are stored for
+/// each frame after the first. The page lists its colors ahead of the frames, which aren't all known until the last
+/// frame is in, so until then the frames are kept as the runs of cells that changed.
+pub struct HtmlEncoder<W: Write> {
+    out: W,
+    rows: usize,
+    cols: usize,
+    previous: Option<Frame>,
+    palette: Vec<String>,
+    palette_ix: HashMap<String, usize>,
+    /// Each frame so far, as how long it's shown for and what changed in it.
+    encoded: Vec<(u64, Vec<Run>)>,
+}
+
+impl<W: Write> HtmlEncoder<W> {
+    /// new starts a page that plays a burn `rows` by `cols` cells, to be written to `out`.
+    pub fn new(out: W, rows: usize, cols: usize) -> Self {
+        HtmlEncoder { out, rows, cols, previous: None, palette: vec![], palette_ix: HashMap::new(), encoded: vec![] }
+    }
+}
+
+impl<W: Write> Encoder for HtmlEncoder<W> {
+    fn frame(&mut self, frame: &Frame, _: Rect, ms: u64) -> io::Result<()> {
+        let runs = runs(self.previous.as_ref(), frame, &mut self.palette, &mut self.palette_ix);
+        self.encoded.push((ms, runs));
+
+        match self.previous {
+            Some(ref mut previous) => previous.copy_from(frame),
+            None => self.previous = Some(frame.clone()),
+        }
+
+        Ok(())
+    }
+
+    fn finish(mut self) -> io::Result<()> {
+        let out = &mut self.out;
+        let color_digits = color_digits(self.palette.len());
+        let frames_json = self.encoded.iter().map(|(ms, runs)| {
+            let runs: Vec<String> = runs.iter()
+                .map(|(row, col, glyphs, colors)| {
+                    format!("[{},{},{},\"{}\"]", row, col, json::string(glyphs), encode_colors(colors, color_digits))
+                })
+                .collect();
+
+            format!("[{},[{}]]", ms, runs.join(","))
+        }).collect::<Vec<String>>().join(",\n");
+
+        let palette_json: Vec<String> = self.palette.iter().map(|color| json::string(color)).collect();
+
+        writeln!(out, "<!DOCTYPE html>")?;
+        writeln!(out, "<html>\n<head>\n<meta charset=\"utf-8\">\n<title>burn</title>")?;
+        writeln!(out, "<style>")?;
+        writeln!(out, "#burn {{ display: inline-block; margin: 0; padding: 1em; background: {}; color: {}; font: 14px/1.2 monospace; }}", hex(BACKGROUND), hex(DEFAULT_FG))?;
+        writeln!(out, "</style>\n</head>\n<body>")?;
+        writeln!(out, "<pre id=\"burn\"></pre>")?;
+        writeln!(out, "<script>")?;
+        writeln!(out, "var ROWS = {}, COLS = {};", self.rows, self.cols)?;
+        writeln!(out, "var DIGITS = \"{}\", COLOR_DIGITS = {};", DIGITS, color_digits)?;
+        writeln!(out, "var PALETTE = [{}];", palette_json.join(","))?;
+        writeln!(out, "var FRAMES = [\n{}\n];", frames_json)?;
+        writeln!(out, "{}", PLAYER)?;
+        writeln!(out, "</script>\n</body>\n</html>")?;
+
+        out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode_colors, runs};
+    use grid::Grid;
+    use layers::Cell;
+    use std::collections::HashMap;
+    use termion::color::Rgb;
+
+    #[test]
+    fn test_runs_only_cover_changes() {
+        let before = Grid::from_rows(1, 4, Cell::plain(' '), vec!["abcd".chars().map(Cell::plain).collect()]);
+        let mut after = before.clone();
+        after[(0, 1)] = Cell::colored('x', Rgb(255, 0, 0));
+
+        let (mut palette, mut palette_ix) = (vec![], HashMap::new());
+
+        assert_eq!(runs(None, &before, &mut palette, &mut palette_ix), vec![(0, 0, "abcd".to_string(), vec![0; 4])]);
+
+        after[(0, 2)] = Cell::colored('y', Rgb(0, 0, 255));
+        assert_eq!(runs(Some(&before), &after, &mut palette, &mut palette_ix), vec![(0, 1, "xy".to_string(), vec![1, 2])]);
+        assert_eq!(palette, vec!["#c8c8c8", "#ff0000", "#0000ff"]);
+        assert_eq!(encode_colors(&[0, 1, 65], 2), "AAABBB");
+    }
+}
@@ -0,0 +1,262 @@
+mod animated_gif;
+mod apng;
+mod font;
+mod html;
+
+use std::io::{self, Write};
+use std::mem;
+use std::time::Duration;
+
+use color_depth::ColorDepth;
+use grid::Grid;
+use layers::{Cell, Compositor};
+use pace::Pace;
+use scene::Scene;
+
+use self::animated_gif::GifEncoder;
+use self::apng::ApngEncoder;
+use self::font::{GLYPH_HEIGHT, GLYPH_WIDTH};
+use self::html::HtmlEncoder;
+
+/// Text without a color of its own is drawn in this, on `BACKGROUND`, like a typical dark terminal.
+const DEFAULT_FG: [u8; 3] = [200, 200, 200];
+const BACKGROUND: [u8; 3] = [0, 0, 0];
+
+/// The animation formats that `burn export` can write.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Format {
+    Gif,
+    Apng,
+    /// A self-contained web page that plays the burn back with a little JavaScript.
+    Html,
+}
+
+type Frame = Grid<Cell>;
+
+/// A rectangle of cells or pixels.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Rect {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+impl Rect {
+    /// in_pixels is this rectangle of cells as drawn in the built-in font.
+    fn in_pixels(self) -> Rect {
+        Rect {
+            x: self.x * GLYPH_WIDTH,
+            y: self.y * GLYPH_HEIGHT,
+            width: self.width * GLYPH_WIDTH,
+            height: self.height * GLYPH_HEIGHT,
+        }
+    }
+}
+
+/// Takes an animation a frame at a time, as the burn plays, and writes it out in some format.
+trait Encoder {
+    /// frame adds `frame`, which stays on screen for `ms` milliseconds. Only the cells in `changed` differ from the
+    /// frame before, which for the first frame is all of them.
+    fn frame(&mut self, frame: &Frame, changed: Rect, ms: u64) -> io::Result<()>;
+
+    /// finish writes out whatever is left once the last frame is in.
+    fn finish(self) -> io::Result<()>;
+}
+
+/// An RGB image.
+struct Raster {
+    width: usize,
+    pixels: Vec<[u8; 3]>,
+}
+
+impl Raster {
+    /// rasterize draws each cell of `frame` with the built-in font, in the cell's own color.
+    fn rasterize(frame: &Frame) -> Self {
+        let (width, height) = (frame.cols() * GLYPH_WIDTH, frame.rows() * GLYPH_HEIGHT);
+        let mut pixels = vec![BACKGROUND; width * height];
+
+        for (i, row) in frame.iter_rows().enumerate() {
+            for (j, cell) in row.iter().enumerate() {
+                let bitmap = font::bitmap(cell.glyph);
+                let fg = cell.fg.map_or(DEFAULT_FG, |rgb| [rgb.0, rgb.1, rgb.2]);
+
+                for y in 0..GLYPH_HEIGHT {
+                    for x in 0..GLYPH_WIDTH {
+                        if bitmap.get(x, y) {
+                            pixels[(i * GLYPH_HEIGHT + y) * width + j * GLYPH_WIDTH + x] = fg;
+                        }
+                    }
+                }
+            }
+        }
+
+        Raster { width, pixels }
+    }
+
+    /// crop copies out `region` as RGBA, leaving any pixel that is the same in `previous` transparent.
+    fn crop(&self, region: Rect, previous: Option<&Raster>) -> Vec<u8> {
+        let mut rgba = Vec::with_capacity(region.width * region.height * 4);
+
+        for y in region.y..region.y + region.height {
+            for x in region.x..region.x + region.width {
+                let ix = y * self.width + x;
+                let [r, g, b] = self.pixels[ix];
+                let unchanged = previous.is_some_and(|previous| previous.pixels[ix] == self.pixels[ix]);
+
+                rgba.extend_from_slice(&[r, g, b, if unchanged { 0 } else { 255 }]);
+            }
+        }
+
+        rgba
+    }
+}
+
+/// record runs `scene`, which is `rows` by `cols` cells, to the end at `pace`, handing each frame to `encoder` as it
+/// would be drawn on a terminal. Each frame stays on screen for a tick, the last one included, and frames identical to
+/// the one before are folded into it, so a frame is only handed over once one that differs comes along. Times are
+/// rounded to the millisecond from the start of the animation, so that rounding doesn't add up over many frames.
+fn record<E: Encoder>(scene: &mut Scene, rows: usize, cols: usize, mut pace: Pace, mut encoder: E) -> io::Result<()> {
+    let compositor = Compositor { rows, cols, color_depth: ColorDepth::TrueColor };
+    let mut shown = Grid::new(rows, cols, Cell::plain(' '));
+    let mut next = shown.clone();
+
+    scene.composite(|layers| compositor.composite_cells_into(layers, &mut shown));
+    let mut changed = Rect { x: 0, y: 0, width: cols, height: rows };
+    let mut shown_at = 0;
+    let mut elapsed = Duration::ZERO;
+
+    loop {
+        elapsed += pace.tick();
+
+        if scene.is_over() {
+            break;
+        }
+
+        scene.step();
+        pace.stepped(scene);
+        scene.composite(|layers| compositor.composite_cells_into(layers, &mut next));
+
+        if let Some(next_changed) = changed_cells(&shown, &next) {
+            let now = elapsed.as_millis();
+            encoder.frame(&shown, changed, (now - shown_at) as u64)?;
+
+            mem::swap(&mut shown, &mut next);
+            changed = next_changed;
+            shown_at = now;
+        }
+    }
+
+    encoder.frame(&shown, changed, (elapsed.as_millis() - shown_at) as u64)?;
+    encoder.finish()
+}
+
+/// changed_cells finds the smallest rectangle of cells holding every difference between `before` and `after`.
+fn changed_cells(before: &Frame, after: &Frame) -> Option<Rect> {
+    let mut bounds: Option<(usize, usize, usize, usize)> = None;
+
+    for (i, (before_row, after_row)) in before.iter_rows().zip(after.iter_rows()).enumerate() {
+        for (j, (before_cell, after_cell)) in before_row.iter().zip(after_row.iter()).enumerate() {
+            if before_cell != after_cell {
+                bounds = Some(match bounds {
+                    None => (j, i, j, i),
+                    Some((left, top, right, bottom)) => (left.min(j), top.min(i), right.max(j), bottom.max(i)),
+                });
+            }
+        }
+    }
+
+    bounds.map(|(left, top, right, bottom)| Rect { x: left, y: top, width: right - left + 1, height: bottom - top + 1 })
+}
+
+/// export runs `scene`, which is `rows` by `cols` cells, to the end and writes it to `out` as an animation that
+/// ticks at `pace`. No terminal is needed, and nothing waits on the clock: the frames are only timed to play back at
+/// that pace. Each frame is written out as the burn gets to it, rather than all of them being held until the end.
+///
+/// GIFs and APNGs are drawn in pixels, and can be at most `u16::MAX` of them a side.
+pub fn export<W: Write>(scene: &mut Scene, rows: usize, cols: usize, pace: Pace, format: Format, out: W) -> io::Result<()> {
+    let (width, height) = (cols * GLYPH_WIDTH, rows * GLYPH_HEIGHT);
+    let max = usize::from(u16::MAX);
+
+    match format {
+        Format::Gif | Format::Apng if width > max || height > max => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{}x{} cells come to {}x{} pixels, more than the {} a side an animation can have", cols, rows, width, height, max),
+        )),
+        Format::Gif => record(scene, rows, cols, pace, GifEncoder::new(out, width, height)?),
+        Format::Apng => record(scene, rows, cols, pace, ApngEncoder::new(out, width, height)),
+        Format::Html => record(scene, rows, cols, pace, HtmlEncoder::new(out, rows, cols)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{changed_cells, export, font, record, Encoder, Format, Frame, Raster, Rect};
+    use config::Config;
+    use grid::Grid;
+    use layers::Cell;
+    use pace::Pace;
+    use scene::Scene;
+    use std::io;
+    use std::time::Duration;
+    use termion::color::Rgb;
+
+    /// Keeps what it's given of each frame.
+    impl Encoder for &mut Vec<(Rect, u64)> {
+        fn frame(&mut self, _: &Frame, changed: Rect, ms: u64) -> io::Result<()> {
+            self.push((changed, ms));
+            Ok(())
+        }
+
+        fn finish(self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_frames_cover_changes_and_rasterize() {
+        let blank = Grid::new(3, 4, Cell::plain(' '));
+        let mut lit = blank.clone();
+        lit[(1, 2)] = Cell::colored('^', Rgb(255, 0, 0));
+        lit[(2, 1)] = Cell::plain('x');
+
+        assert_eq!(changed_cells(&blank, &blank), None);
+        assert_eq!(changed_cells(&blank, &lit), Some(Rect { x: 1, y: 1, width: 2, height: 2 }));
+        assert_eq!(Rect { x: 1, y: 1, width: 2, height: 2 }.in_pixels(), Rect { x: 8, y: 13, width: 16, height: 26 });
+
+        let raster = Raster::rasterize(&Grid::from_rows(1, 2, Cell::plain(' '), vec![vec![Cell::colored('A', Rgb(255, 0, 0)), Cell::plain('━')]]));
+        assert_eq!((raster.width, raster.pixels.len()), (16, 16 * 13));
+        assert!(raster.pixels.contains(&[255, 0, 0]));
+
+        // every tick is accounted for, whether its frame changed or was folded into the one before
+        let config = Config::default();
+        let mut scene = Scene::from_text("embers", 6, 10, &config, 2).unwrap();
+        let mut frames = vec![];
+        record(&mut scene, 6, 10, Pace::every(Duration::from_millis(30)), &mut frames).unwrap();
+
+        assert_eq!(frames[0].0, Rect { x: 0, y: 0, width: 10, height: 6 });
+        assert!(frames.len() < scene.ticks() as usize + 1);
+        assert!(frames.iter().all(|&(_, ms)| ms > 0 && ms % 30 == 0));
+        assert_eq!(frames.iter().map(|&(_, ms)| ms).sum::<u64>(), 30 * (scene.ticks() + 1));
+
+        // a GIF or APNG can't be drawn any wider than its sizes can be written down
+        let mut scene = Scene::from_text("", 4, 8200, &config, 2).unwrap();
+        assert!(export(&mut scene, 4, 8200, Pace::every(Duration::from_millis(30)), Format::Gif, io::sink()).is_err());
+        assert!(export(&mut scene, 4, 8200, Pace::every(Duration::from_millis(30)), Format::Apng, io::sink()).is_err());
+        assert_eq!(scene.ticks(), 0);
+    }
+
+    #[test]
+    fn test_box_drawing_glyphs_connect() {
+        let corner = font::bitmap('╔');
+        let across = font::bitmap('═');
+
+        // both lines of a double stroke run off the edge of the cell so they join up with their neighbors
+        for &y in &[4, 8] {
+            assert!(across.get(0, y) && across.get(font::GLYPH_WIDTH - 1, y));
+            assert!(corner.get(font::GLYPH_WIDTH - 1, y));
+        }
+        assert!(corner.get(1, font::GLYPH_HEIGHT - 1) && corner.get(5, font::GLYPH_HEIGHT - 1));
+        assert!(!corner.get(0, 4) && !corner.get(1, 0));
+    }
+}
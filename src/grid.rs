@@ -0,0 +1,114 @@
+use std::ops::{Index, IndexMut};
+
+use rayon::prelude::*;
+use rayon::slice::ChunksMut;
+
+/// A fixed-size two-dimensional grid stored as one flat, row-major `Vec`, so that a whole grid can be cleared or copied
+/// in one go and stepping the simulation never has to allocate.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Grid<T> {
+    rows: usize,
+    cols: usize,
+    cells: Vec<T>,
+}
+
+impl<T: Copy> Grid<T> {
+    pub fn new(rows: usize, cols: usize, fill: T) -> Self {
+        Grid {
+            rows,
+            cols,
+            cells: vec![fill; rows * cols],
+        }
+    }
+
+    /// from_rows builds a grid `rows` by `cols` from ragged rows, filling anything they don't cover with `fill`.
+    pub fn from_rows(rows: usize, cols: usize, fill: T, source: Vec<Vec<T>>) -> Self {
+        let mut grid = Self::new(rows, cols, fill);
+
+        for (i, row) in source.into_iter().take(rows).enumerate() {
+            for (j, cell) in row.into_iter().take(cols).enumerate() {
+                grid[(i, j)] = cell;
+            }
+        }
+
+        grid
+    }
+
+    pub fn fill(&mut self, value: T) {
+        for cell in self.cells.iter_mut() {
+            *cell = value;
+        }
+    }
+
+    /// copy_from overwrites this grid with `other`, which must be the same size.
+    pub fn copy_from(&mut self, other: &Grid<T>) {
+        self.cells.copy_from_slice(&other.cells);
+    }
+}
+
+impl<T> Grid<T> {
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn row(&self, row: usize) -> &[T] {
+        &self.cells[row * self.cols..(row + 1) * self.cols]
+    }
+
+    /// iter_rows goes through the grid a row at a time, top first.
+    pub fn iter_rows(&self) -> impl Iterator<Item = &[T]> {
+        // `max(1)` keeps `chunks` happy on a grid with no columns, which then has no rows to give either
+        self.cells.chunks(self.cols.max(1))
+    }
+
+    /// iter goes through every cell, row by row.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.cells.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.cells.iter_mut()
+    }
+}
+
+impl<T: Send> Grid<T> {
+    /// par_bands_mut splits the grid into bands of `rows_per_band` whole rows, the last of which may be shorter, that
+    /// can be written to in parallel. Each band comes as a flat slice of its rows.
+    pub fn par_bands_mut(&mut self, rows_per_band: usize) -> ChunksMut<'_, T> {
+        self.cells.par_chunks_mut((rows_per_band * self.cols).max(1))
+    }
+}
+
+impl<T> Index<(usize, usize)> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, (row, col): (usize, usize)) -> &T {
+        debug_assert!(row < self.rows && col < self.cols);
+        &self.cells[row * self.cols + col]
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for Grid<T> {
+    fn index_mut(&mut self, (row, col): (usize, usize)) -> &mut T {
+        debug_assert!(row < self.rows && col < self.cols);
+        &mut self.cells[row * self.cols + col]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Grid;
+
+    #[test]
+    fn test_from_rows_pads_and_clips() {
+        let grid = Grid::from_rows(3, 2, '.', vec![vec!['a', 'b', 'c'], vec!['d']]);
+
+        let rows: Vec<String> = grid.iter_rows().map(|row| row.iter().collect()).collect();
+        assert_eq!(rows, vec!["ab", "d.", ".."]);
+        assert_eq!(grid[(1, 0)], 'd');
+    }
+}
@@ -1,131 +1,213 @@
-use state::FireState;
+use termion::color;
 
+use color_depth::ColorDepth;
+use grid::Grid;
+
+/// A single character cell: a glyph plus an optional foreground color.
+/// `None` for `fg` leaves the terminal's default color in place.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Cell {
+    pub glyph: char,
+    pub fg: Option<color::Rgb>,
+}
+
+impl Cell {
+    pub fn plain(glyph: char) -> Self {
+        Cell { glyph, fg: None }
+    }
+
+    pub fn colored(glyph: char, fg: color::Rgb) -> Self {
+        Cell { glyph, fg: Some(fg) }
+    }
+
+    fn write_to(&self, buf: &mut Vec<u8>, color_depth: ColorDepth) {
+        color_depth.paint(buf, self.glyph, self.fg).expect("writing to a Vec cannot fail");
+    }
+}
+
+/// Something that can be composited: a grid of cells, `rows` by `cols`, placed on the canvas with its top left corner
+/// at `origin`. Rows and columns passed to a layer's methods count from its own top left corner, not the canvas'.
+///
+/// The fire, ash and smoke layers are made once, along with the scene, and each state renders into its own layer in
+/// place whenever the scene is composited, so that a burn doesn't allocate from one frame to the next.
 pub trait Layerable {
     fn rows(&self) -> usize;
     fn cols(&self) -> usize;
-    fn features(&self) -> &Vec<Vec<Option<String>>>;
+    fn features(&self) -> &Grid<Option<Cell>>;
 
-    fn get(&self, row_ix: usize, col_ix: usize) -> Option<String> {
-        assert!(row_ix < self.rows());
-        assert!(col_ix < self.cols());
+    /// origin is where the layer's top left corner sits on the canvas, as (row, col). It may be off the canvas, in
+    /// which case only the part of the layer that's on it is drawn. By default, layers sit at the canvas' corner.
+    fn origin(&self) -> (isize, isize) {
+        (0, 0)
+    }
 
-        if row_ix >= self.features().len() {
+    /// get is the cell at (`row_ix`, `col_ix`), or `None` outside of the layer.
+    fn get(&self, row_ix: usize, col_ix: usize) -> Option<Cell> {
+        if row_ix >= self.rows() || col_ix >= self.cols() {
             return None;
         }
 
-        let row = &self.features()[row_ix];
-
-        if col_ix >= row.len() {
-            return None;
-        }
+        self.features()[(row_ix, col_ix)]
+    }
 
-        let elt = row[col_ix].clone();
-        return elt;
+    /// blend combines this layer's cell at (`row_ix`, `col_ix`) with `below`, the result of compositing every
+    /// layer underneath it. By default a present cell hides whatever is below it. The compositor only blends cells
+    /// inside the layer; everywhere else, what's below shows through.
+    fn blend(&self, row_ix: usize, col_ix: usize, below: Option<Cell>) -> Option<Cell> {
+        self.get(row_ix, col_ix).or(below)
     }
 }
 
+#[derive(Clone)]
 pub struct BasicLayer {
-    rows: usize,
-    cols: usize,
-    features: Vec<Vec<Option<String>>>,
+    features: Grid<Option<Cell>>,
+    origin: (isize, isize),
 }
 
 impl BasicLayer {
-    pub fn create(rows: usize, cols: usize, features: Vec<Vec<Option<String>>>) -> Self {
+    /// create builds a layer `rows` by `cols` from rows of cells, which may be ragged or run long. Whatever they don't
+    /// cover is left empty, and whatever falls outside the layer is dropped.
+    pub fn create(rows: usize, cols: usize, features: Vec<Vec<Option<Cell>>>) -> Self {
         BasicLayer {
-            rows,
-            cols,
-            features,
+            features: Grid::from_rows(rows, cols, None, features),
+            origin: (0, 0),
         }
     }
+
+    /// Moves the layer so that its top left corner is at (`row`, `col`) on the canvas.
+    pub fn set_origin(&mut self, row: isize, col: isize) {
+        self.origin = (row, col);
+    }
 }
 
 impl Layerable for BasicLayer {
-    fn rows(&self) -> usize { self.rows }
-    fn cols(&self) -> usize { self.cols }
-    fn features(&self) -> &Vec<Vec<Option<String>>> {
+    fn rows(&self) -> usize { self.features.rows() }
+    fn cols(&self) -> usize { self.features.cols() }
+    fn features(&self) -> &Grid<Option<Cell>> {
         &self.features
     }
+    fn origin(&self) -> (isize, isize) { self.origin }
 }
 
+/// Flattens layers onto a canvas `rows` by `cols`. Layers can be any size and anywhere; whatever falls outside the
+/// canvas is clipped.
 pub struct Compositor {
     pub rows: usize,
     pub cols: usize,
+    pub color_depth: ColorDepth,
 }
 
 impl Compositor {
-    /// composite produces a single field of bytes based on the presence of bytes at each index in each of the `layers`.
+    /// composite produces a single field of bytes based on the presence of cells at each index in each of the `layers`.
     /// Layers in `layers` should be ordered by ascending precedence (i.e., bottom layers first).
-    pub fn composite(&self, layers: &[&Layerable]) -> Vec<Vec<u8>> {
-        let mut field = vec![vec![" ".to_string(); self.cols]; self.rows];
+    pub fn composite(&self, layers: &[&dyn Layerable]) -> Vec<Vec<u8>> {
+        let mut field = vec![];
+        self.composite_into(layers, &mut field);
+
+        field
+    }
+
+    /// composite_into is like `composite`, but writes into `field`, reusing the lines already in it so that drawing
+    /// frame after frame doesn't allocate.
+    pub fn composite_into(&self, layers: &[&dyn Layerable], field: &mut Vec<Vec<u8>>) {
+        field.resize_with(self.rows, Vec::new);
+
+        for (i, line) in field.iter_mut().enumerate() {
+            line.clear();
 
-        for i in 0..self.rows {
             for j in 0..self.cols {
-                let comped: Option<String> = layers.into_iter().rev().fold(None, |acc, layer| acc.or(layer.get(i, j)));
-                if let Some(string) = comped {
-                    field[i][j] = string;
-                }
+                self.cell_at(layers, i, j).write_to(line, self.color_depth);
             }
         }
+    }
 
-        let byte_field: Vec<Vec<u8>> = field.into_iter().map(|row| {
-            row.into_iter().flat_map(|s| s.into_bytes().into_iter()).collect()
-        }).collect();
-
-        byte_field
+    /// composite_cells is like `composite`, but leaves the result as cells rather than writing them out. Blank
+    /// cells are filled in with spaces.
+    pub fn composite_cells(&self, layers: &[&dyn Layerable]) -> Vec<Vec<Cell>> {
+        (0..self.rows).map(|i| (0..self.cols).map(|j| self.cell_at(layers, i, j)).collect()).collect()
     }
 
-    pub fn intermediate_composite(&self, layers: &[&Layerable]) -> IntermediateLayer {
-        let mut field: Vec<Vec<Option<String>>> = vec![vec![None; self.cols]; self.rows];
+    /// composite_cells_into is like `composite_cells`, but writes into `cells`, which is resized to fit if need be.
+    pub fn composite_cells_into(&self, layers: &[&dyn Layerable], cells: &mut Grid<Cell>) {
+        if cells.rows() != self.rows || cells.cols() != self.cols {
+            *cells = Grid::new(self.rows, self.cols, Cell::plain(' '));
+        }
 
         for i in 0..self.rows {
             for j in 0..self.cols {
-                let comped: Option<String> = layers.into_iter().rev().fold(None, |acc, layer| acc.or(layer.get(i, j)));
-                field[i][j] = comped;
+                cells[(i, j)] = self.cell_at(layers, i, j);
             }
         }
+    }
 
-        IntermediateLayer {
-            rows: self.rows,
-            cols: self.cols,
-            features: field,
-        }
+    fn cell_at(&self, layers: &[&dyn Layerable], row_ix: usize, col_ix: usize) -> Cell {
+        layers.iter()
+            .fold(None, |acc, layer| match local(*layer, row_ix, col_ix) {
+                Some((row, col)) => layer.blend(row, col, acc),
+                None => acc,
+            })
+            .unwrap_or(Cell::plain(' '))
     }
 }
 
-pub struct IntermediateLayer {
-    rows: usize,
-    cols: usize,
-    features: Vec<Vec<Option<String>>>,
-}
+/// local translates (`row_ix`, `col_ix`) on the canvas into `layer`'s own rows and columns, if it falls inside it.
+fn local(layer: &dyn Layerable, row_ix: usize, col_ix: usize) -> Option<(usize, usize)> {
+    let (origin_row, origin_col) = layer.origin();
+    let row = row_ix as isize - origin_row;
+    let col = col_ix as isize - origin_col;
+
+    if row < 0 || col < 0 || row as usize >= layer.rows() || col as usize >= layer.cols() {
+        return None;
+    }
 
-impl Layerable for IntermediateLayer {
-    fn rows(&self) -> usize { self.rows }
-    fn cols(&self) -> usize { self.cols }
-    fn features(&self) -> &Vec<Vec<Option<String>>> { &self.features }
+    Some((row as usize, col as usize))
 }
 
 #[cfg(test)]
 mod tests {
-    use super::BasicLayer;
-    use super::Layerable;
+    use super::{BasicLayer, Cell, Compositor, Layerable};
+    use color_depth::ColorDepth;
 
     #[test]
     pub fn test_get() {
-        let features: Vec<Vec<Option<u8>>> = vec![
-            b"bonjour".map(|&x| Some(x)).collect(),
-            b"allo".map(|&x| Some(x)).collect(),
+        let features: Vec<Vec<Option<Cell>>> = vec![
+            "bonjour".chars().map(|c| Some(Cell::plain(c))).collect(),
+            "allo".chars().map(|c| Some(Cell::plain(c))).collect(),
         ];
 
-        let layer = BasicLayer {
-            features,
-            rows: 3,
-            cols: 7,
-        };
+        let layer = BasicLayer::create(3, 7, features);
 
-        assert_eq!(layer.get(0, 2), Some(b'n'));
-        assert_eq!(layer.get(1, 0), Some(b'a'));
+        assert_eq!(layer.get(0, 2), Some(Cell::plain('n')));
+        assert_eq!(layer.get(1, 0), Some(Cell::plain('a')));
         assert_eq!(layer.get(2, 0), None);
         assert_eq!(layer.get(2, 5), None);
+        assert_eq!(layer.get(3, 0), None);
+        assert_eq!(layer.get(0, 7), None);
+    }
+
+    #[test]
+    pub fn test_composite_clips_layers_to_canvas() {
+        let page = BasicLayer::create(3, 5, vec![]);
+        let sprite = |row, col| {
+            let mut layer = BasicLayer::create(2, 2, vec!["ab".chars().map(|c| Some(Cell::plain(c))).collect(); 2]);
+            layer.set_origin(row, col);
+            layer
+        };
+        let (corner, edge, outside) = (sprite(-1, -1), sprite(2, 4), sprite(10, -8));
+
+        let compositor = Compositor { rows: 3, cols: 5, color_depth: ColorDepth::TrueColor };
+        let canvas = compositor.composite(&[&page, &corner, &edge, &outside]);
+
+        assert_eq!(canvas, vec![b"b    ".to_vec(), b"     ".to_vec(), b"    a".to_vec()]);
+    }
+
+    #[test]
+    pub fn test_composite_precedence() {
+        let bottom = BasicLayer::create(1, 3, vec!["abc".chars().map(|c| Some(Cell::plain(c))).collect()]);
+        let top = BasicLayer::create(1, 3, vec![vec![None, Some(Cell::plain('X')), None]]);
+
+        let compositor = Compositor { rows: 1, cols: 3, color_depth: ColorDepth::TrueColor };
+
+        assert_eq!(compositor.composite(&[&bottom, &top]), vec![b"aXc".to_vec()]);
     }
 }
@@ -0,0 +1,75 @@
+//! The pieces `burn` is built from: the combustion simulation, the layers it is drawn as, and the ways of getting
+//! those onto a terminal or into a file. The `burn` binary is a thin front end to these, and the benchmarks drive them
+//! directly.
+//!
+//! A [`Scene`] is the usual way in. Build one from some text, step it as often as you like, and draw it to anything
+//! that implements `Write`:
+//!
+//! ```
+//! use burn::{ColorDepth, Config, Scene};
+//!
+//! let config = Config::default();
+//! let mut scene = Scene::from_text("Dear diary,\ntoday I...", 10, 30, &config, 7).unwrap();
+//!
+//! while !scene.is_over() {
+//!     scene.step();
+//! }
+//!
+//! let mut frame = vec![];
+//! scene.write_frame(ColorDepth::Monochrome, &mut frame).unwrap();
+//! assert_eq!(frame.split(|&byte| byte == b'\n').filter(|line| !line.is_empty()).count(), 10);
+//! ```
+//!
+//! To animate a burn, hand a scene and a [`backend::Backend`] to [`player::play`], which paces the simulation. The
+//! terminal backends, such as [`ui::Ui`], put the terminal back afterwards, and [`backend::MemoryBackend`] keeps every
+//! frame for checking. For finer control, [`Scene::composite`] passes the scene's layers to a
+//! [`Compositor`], and [`CombustionState`] is the simulation alone, for drawing in some other way entirely.
+//!
+//! A scene's layers are a named stack that can be rearranged: [`Scene::add_layer`] puts anything [`Layerable`] on it,
+//! such as a HUD, and layers can be moved, taken out or hidden.
+//!
+//! With the `ratatui` feature, `widget::BurnWidget` burns text inside a ratatui layout.
+
+#[cfg(feature = "crossterm")]
+extern crate crossterm;
+extern crate gif;
+extern crate libc;
+extern crate png;
+#[cfg(test)]
+extern crate proptest;
+#[cfg(feature = "ratatui")]
+extern crate ratatui;
+extern crate rayon;
+extern crate serde;
+extern crate signal_hook;
+extern crate termion;
+extern crate toml;
+
+pub mod asciicast;
+pub mod backend;
+pub mod color_depth;
+pub mod config;
+pub mod export;
+pub mod grid;
+mod json;
+pub mod layers;
+mod border;
+mod noise;
+pub mod outro;
+pub mod pace;
+mod palette;
+pub mod player;
+pub mod scene;
+pub mod scheduler;
+pub mod sim;
+pub mod state;
+pub mod theme;
+pub mod ui;
+#[cfg(feature = "ratatui")]
+pub mod widget;
+
+pub use color_depth::ColorDepth;
+pub use config::Config;
+pub use layers::{Cell, Compositor, Layerable};
+pub use scene::Scene;
+pub use state::{CombustionState, Stats};
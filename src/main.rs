@@ -1,29 +1,38 @@
+extern crate burn;
+extern crate libc;
 extern crate rand;
 extern crate termion;
 
-use std::env;
+mod cli;
+
 use std::error::Error;
+use std::ffi::CString;
 use std::fmt;
 use std::fs;
 use std::io;
 use std::io::prelude::*;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
 use std::process;
-use std::time;
-
-use termion::raw::IntoRawMode;
+use std::time::Duration;
 
-mod layers;
-mod border;
-mod state;
-mod ui;
-
-use layers::{BasicLayer, Compositor, Layerable};
-use border::Border;
-use state::{FireState, FireLayer};
-use ui::Ui;
+use burn::asciicast::AsciicastWriter;
+use burn::color_depth::ColorDepth;
+use burn::config::Config;
+use burn::export;
+use burn::grid::Grid;
+use burn::layers::Cell;
+use burn::outro::{self, Outro};
+use burn::pace::Pace;
+use burn::player::{self, Interrupted};
+use burn::scene::Scene;
+use burn::sim;
+use burn::ui::Ui;
+use cli::{Command, InlineRows, Options};
 
 #[derive(Debug)]
-struct BurnError(&'static str);
+struct BurnError(String);
 
 impl fmt::Display for BurnError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -33,7 +42,25 @@ impl fmt::Display for BurnError {
 
 impl Error for BurnError {
     fn description(&self) -> &str {
-        self.0
+        &self.0
+    }
+}
+
+impl From<String> for BurnError {
+    fn from(message: String) -> Self {
+        BurnError(message)
+    }
+}
+
+impl From<Interrupted> for BurnError {
+    fn from(_: Interrupted) -> Self {
+        BurnError("interrupted".to_string())
+    }
+}
+
+impl<'a> From<&'a str> for BurnError {
+    fn from(message: &'a str) -> Self {
+        BurnError(message.to_string())
     }
 }
 
@@ -43,95 +70,213 @@ fn main() {
     match try_main() {
         Ok(()) => process::exit(0),
         Err(error) => {
-            println!("{}", error.description());
+            println!("{}", error.0);
             process::exit(1)
         }
     }
 }
 
 fn try_main() -> Result<()> {
+    let options = Options::parse()?;
+
+    let mut config = Config::load()?;
+    options.apply(&mut config);
+    config.validate()?;
+
+    let seed = options.seed.unwrap_or_else(rand::random);
+
+    match options.command {
+        Command::Burn { ref filepath } => burn(filepath, options.delete, seed, options.duration, options.inline, &config),
+        Command::Record { ref output, ref filepath } => record(output, filepath, options.size, seed, options.duration, &config),
+        Command::Export { ref output, ref filepath, format } => {
+            export(output, filepath, format, options.size, seed, options.duration, &config)
+        },
+        Command::Sim { ref filepath, frames, format, ref final_frame } => {
+            sim(filepath.as_deref(), frames, format, final_frame.as_deref(), options.size, seed, &config)
+        },
+        Command::PrintConfig => {
+            print!("{}", config.to_toml());
+            Ok(())
+        },
+    }
+}
+
+/// burn plays the burn on the terminal, then deletes the file if `delete` is set. It takes over the whole screen,
+/// unless `inline` is given, in which case it burns in rows set aside below the cursor and leaves the last frame there.
+fn burn(filepath: &str, delete: bool, seed: u64, duration: Option<Duration>, inline: Option<InlineRows>, config: &Config) -> Result<()> {
+    let mut file = fs::File::open(filepath).map_err(|_| "failed to open file")?;
+
+    if delete {
+        check_can_unlink_file(Path::new(filepath))?;
+    }
+
+    let mut text = vec![];
+    file.read_to_end(&mut text).map_err(|_| "failed to read file")?;
+
+    // TODO: add max bounds on term width to make it look like a piece of paper
+    let (term_cols, term_rows) = termion::terminal_size().expect("could not read terminal size");
+    let color_depth = config.color_depth()?.unwrap_or_else(ColorDepth::detect);
+
+    let (mut term_rows, term_cols) = (term_rows as usize, term_cols as usize);
+
+    // the page of an inline burn stops a row short of the bottom of the terminal, so that drawing its last line doesn't
+    // scroll its first out of reach. Like a full-screen one, it's two rows shorter than the terminal it's laid out for
+    let page_rows = inline.map(|inline| {
+        let rows = match inline {
+            InlineRows::Fit => text.lines().count() + 2,
+            InlineRows::Exactly(rows) => rows,
+        };
+
+        rows.clamp(3, term_rows.saturating_sub(1).max(3))
+    });
+
+    if let Some(page_rows) = page_rows {
+        term_rows = page_rows + 2;
+    }
+
+    let mut scene = load_scene(&text, term_rows, term_cols, seed, config)?;
+    let pace = Pace::new(&scene, duration, &config.rendering);
+
     let stdout = io::stdout();
-    let stdout = stdout.lock(); //.into_raw_mode().unwrap();
+    let ui = match page_rows {
+        Some(page_rows) => Ui::inline(stdout.lock(), page_rows, color_depth),
+        None => Ui::fullscreen(stdout.lock(), color_depth),
+    };
+    let ui = ui.map_err(|err| format!("failed to set up the terminal: {}", err))?;
 
-    let filepath = get_filepath()?;
+    // an interrupted burn comes back as an error, so the file is only deleted once it has burned all the way
+    let elapsed = player::play(ui, &mut scene, pace, &config.rendering)?;
+    print_summary(&scene, elapsed, config);
 
-    match fs::File::open(filepath) {
-        Ok(file) => {
-            // TODO: add max bounds on term width to make it look like a piece of paper
-            let (term_cols, term_rows) = termion::terminal_size().expect("could not read terminal size");
-            let (term_cols, term_rows) = ((term_cols - 2) as usize, (term_rows - 2) as usize);
+    if delete {
+        fs::remove_file(filepath).map_err(|err| format!("failed to delete file: {}", err))?;
+    }
 
-            let filebuf = io::BufReader::new(file);
+    Ok(())
+}
 
-            let file_lines: Vec<Vec<u8>> = filebuf.lines()
-                .take(term_rows)
-                .map(|maybe_line| maybe_line.map(|line| line.into_bytes()).unwrap())
-                .collect();
+/// record plays the burn into an asciicast at `output`. The recording is `size` (columns, rows), or the size of
+/// the terminal if none is given. Unless a color depth is configured, it is recorded in true color, since the
+/// terminal it will be played back in is unknown.
+fn record(output: &str, filepath: &str, size: Option<(usize, usize)>, seed: u64, duration: Option<Duration>, config: &Config) -> Result<()> {
+    let text = fs::read(filepath).map_err(|_| "failed to open file")?;
 
-            let compositor = Compositor {
-                rows: term_rows,
-                cols: term_cols,
-            };
+    let (cols, rows) = size.unwrap_or_else(recording_size);
+    let color_depth = config.color_depth()?.unwrap_or(ColorDepth::TrueColor);
 
-            let base_layer = BasicLayer::create(
-                term_rows,
-                term_cols,
-                file_lines.into_iter().map(|row| row.into_iter().map(|byte| format!("{}", byte as char).into()).collect()).collect(),
-            );
+    let mut scene = load_scene(&text, rows, cols, seed, config)?;
+    let pace = Pace::new(&scene, duration, &config.rendering);
 
-            let mut ui = Ui::create(stdout);
-            let mut state = state::CombustionState::new(term_rows, term_cols);
-            let border = Border::new(term_rows, term_cols);
+    let cast = fs::File::create(output).map_err(|err| format!("failed to create {}: {}", output, err))?;
+    let writer = AsciicastWriter::new(io::BufWriter::new(cast), cols, rows)
+        .map_err(|err| format!("failed to write {}: {}", output, err))?;
 
-            state.start_fire();
+    let elapsed = player::play(Ui::create(writer, color_depth), &mut scene, pace, &config.rendering)?;
+    print_summary(&scene, elapsed, config);
 
-            // TODO: yuck. Make this expression nicer, maybe allow composing the compositor into the ui from the get-go
-            ui.draw(&compositor.composite(&[&base_layer, &border, &(state.as_layer())]));
-            let mut last_tick = time::Instant::now();
-            let mut state_is_stale = true;
+    Ok(())
+}
 
-            let frame_wait = time::Duration::from_millis(100);
+/// export writes the burn as an animation at `output`. Like a recording, it is `size` (columns, rows) or the size of
+/// the terminal, and its ticks are paced the same way, but nothing is drawn on the terminal and nothing waits on the
+/// clock: each frame is a tick, timed to play back at that pace, so it runs as fast as it can.
+fn export(
+    output: &str,
+    filepath: &str,
+    format: export::Format,
+    size: Option<(usize, usize)>,
+    seed: u64,
+    duration: Option<Duration>,
+    config: &Config,
+) -> Result<()> {
+    let text = fs::read(filepath).map_err(|_| "failed to open file")?;
+    let (cols, rows) = size.unwrap_or_else(recording_size);
 
-            while !state.is_saturated() {
-                if state_is_stale {
-                    state = state.get_next();
-                    state_is_stale = false;
-                }
+    let mut scene = load_scene(&text, rows, cols, seed, config)?;
+    let pace = Pace::new(&scene, duration, &config.rendering);
 
-                let now = time::Instant::now();
+    let out = fs::File::create(output).map_err(|err| format!("failed to create {}: {}", output, err))?;
+    export::export(&mut scene, rows - 2, cols - 2, pace, format, io::BufWriter::new(out))
+        .map_err(|err| format!("failed to write {}: {}", output, err))?;
 
-                if now.duration_since(last_tick) >= frame_wait {
-                    ui.draw(&compositor.composite(&[&base_layer, &border, &(state.as_layer())]));
-                    state_is_stale = true;
-                    last_tick = now;
-                }
-            }
+    Ok(())
+}
 
-            Ok(())
-        }
-        Err(_) => {
-            Err(BurnError("failed to open file"))
-        }
+/// sim runs the burn without a terminal and prints statistics for each frame. Unlike recording or exporting, the
+/// terminal size is never consulted, so a run is reproducible anywhere given a seed: the page is `size` or 80x24.
+fn sim(
+    filepath: Option<&str>,
+    frames: Option<usize>,
+    format: sim::StatsFormat,
+    final_frame: Option<&str>,
+    size: Option<(usize, usize)>,
+    seed: u64,
+    config: &Config,
+) -> Result<()> {
+    let (cols, rows) = size.unwrap_or((80, 24));
+
+    let text = match filepath {
+        Some(filepath) => fs::read(filepath).map_err(|_| "failed to open file")?,
+        None => vec![],
+    };
+    let mut scene = load_scene(&text, rows, cols, seed, config)?;
+
+    let stats = sim::run(&mut scene, frames);
+
+    let stdout = io::stdout();
+    sim::write_stats(&stats, format, stdout.lock()).map_err(|err| format!("failed to write statistics: {}", err))?;
+
+    if let Some(final_frame) = final_frame {
+        let mut frame = Grid::new(rows - 2, cols - 2, Cell::plain(' '));
+        fs::write(final_frame, sim::plain_text(&mut scene, rows - 2, cols - 2, &mut frame))
+            .map_err(|err| format!("failed to write {}: {}", final_frame, err))?;
     }
+
+    Ok(())
+}
+
+/// recording_size is the size of the terminal, if there is one, or a standard 80x24 otherwise.
+fn recording_size() -> (usize, usize) {
+    termion::terminal_size().map(|(cols, rows)| (cols as usize, rows as usize)).unwrap_or((80, 24))
 }
 
-fn get_filepath() -> Result<String> {
-    let mut args = env::args();
+/// load_scene lays `text` out on a page that fills a terminal `term_rows` by `term_cols`.
+fn load_scene(text: &[u8], term_rows: usize, term_cols: usize, seed: u64, config: &Config) -> Result<Scene> {
+    let text = std::str::from_utf8(text).map_err(|_| "file is not valid UTF-8")?;
 
-    match args.len() {
-        2 => Ok(args.nth(1).unwrap()),
-        _ => Err(BurnError("`burn` should be called with a single filepath."))
+    Ok(Scene::from_text(text, term_rows.saturating_sub(2), term_cols.saturating_sub(2), config, seed)?)
+}
+
+/// print_summary prints how the burn went, which took `elapsed`, if the outro calls for it.
+fn print_summary(scene: &Scene, elapsed: Duration, config: &Config) {
+    if config.rendering.outro == Outro::Summary {
+        println!("{}", outro::summary(&scene.stats(), scene.ticks(), elapsed, scene.rows(), scene.cols()));
     }
 }
 
-// TODO: this doesn't actually check whether the file can be unlinked in UNIX.
-// either remove this altogether and rely on OS exception or read parent dir permissions too
-fn check_can_unlink_file(file: fs::File) -> Result<()> {
-    let metadata = file.metadata().expect("failed to read file metadata");
+/// check_can_unlink_file fails early, before anything has burned, if the file at `path` couldn't be deleted. Unlinking
+/// a file takes write and search permission on the directory it's in, not on the file itself, and a directory with
+/// the sticky bit set, like `/tmp`, also only lets the owner of a file, or of the directory, unlink it.
+fn check_can_unlink_file(path: &Path) -> Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if dir != Path::new("") => dir,
+        _ => Path::new("."),
+    };
+    let dir_path = CString::new(dir.as_os_str().as_bytes()).map_err(|_| "invalid file path")?;
+
+    // AT_EACCESS checks as the effective user, who is the one that will be deleting the file
+    let writable = unsafe { libc::faccessat(libc::AT_FDCWD, dir_path.as_ptr(), libc::W_OK | libc::X_OK, libc::AT_EACCESS) == 0 };
+    if !writable {
+        return Err(format!("can't delete the file: {} isn't writable", dir.display()).into());
+    }
+
+    let dir_metadata = fs::metadata(dir).map_err(|err| format!("failed to read {}: {}", dir.display(), err))?;
+    let file_metadata = fs::symlink_metadata(path).map_err(|err| format!("failed to read file metadata: {}", err))?;
+    let uid = unsafe { libc::geteuid() };
 
-    if metadata.permissions().readonly() {
-        Err(BurnError("file lacks write permission"))
-    } else {
-        Ok(())
+    if dir_metadata.mode() & libc::S_ISVTX != 0 && uid != 0 && uid != file_metadata.uid() && uid != dir_metadata.uid() {
+        return Err(format!("can't delete the file: only its owner can, since {} is sticky", dir.display()).into());
     }
+
+    Ok(())
 }
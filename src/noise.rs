@@ -0,0 +1,56 @@
+/// hash maps a lattice point to a pseudo-random value in `0.0..1.0`.
+fn hash(x: i64, y: i64, z: i64) -> f64 {
+    let mut h = (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+        ^ (z as u64).wrapping_mul(0x1656_67B1_9E37_79F9);
+
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xFF51_AFD7_ED55_8CCD);
+    h ^= h >> 33;
+
+    (h >> 11) as f64 / (1u64 << 53) as f64
+}
+
+fn smoothstep(t: f64) -> f64 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+/// value_noise samples smooth 3D value noise in `0.0..1.0`. Nearby points get similar values, so it can
+/// be used to vary things coherently across space and time.
+pub fn value_noise(x: f64, y: f64, z: f64) -> f64 {
+    let (x0, y0, z0) = (x.floor(), y.floor(), z.floor());
+    let (tx, ty, tz) = (smoothstep(x - x0), smoothstep(y - y0), smoothstep(z - z0));
+    let (x0, y0, z0) = (x0 as i64, y0 as i64, z0 as i64);
+
+    let corner = |dx: i64, dy: i64, dz: i64| hash(x0 + dx, y0 + dy, z0 + dz);
+    let plane = |dz: i64| {
+        lerp(
+            lerp(corner(0, 0, dz), corner(1, 0, dz), tx),
+            lerp(corner(0, 1, dz), corner(1, 1, dz), tx),
+            ty,
+        )
+    };
+
+    lerp(plane(0), plane(1), tz)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::value_noise;
+
+    #[test]
+    fn test_value_noise_is_bounded_and_continuous() {
+        for i in 0..200 {
+            let x = i as f64 * 0.173;
+            let here = value_noise(x, 1.5, -2.25);
+            let nearby = value_noise(x + 0.01, 1.5, -2.25);
+
+            assert!((0.0..1.0).contains(&here));
+            assert!((here - nearby).abs() < 0.05);
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use termion::color::Rgb;
+
+use grid::Grid;
+use layers::{Cell, Layerable};
+use noise;
+use palette;
+use state::Stats;
+
+/// Size, in cells, of the patches that blank out together as the page fades.
+const FADE_SCALE: f64 = 4.0;
+
+/// What happens once the burn is over.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outro {
+    /// Stop as soon as the burn is over.
+    None,
+    /// Leave the last frame, ash and all, up for a while.
+    Hold,
+    /// Darken the last frame and blank it out, a patch at a time.
+    Fade,
+    /// Hold the last frame, then print a summary of the burn.
+    Summary,
+}
+
+/// Drawn over the rest of the scene to fade it out. At a `progress` of `0.0` everything shows through unchanged, and
+/// at `1.0` the page is blank.
+pub struct FadeLayer {
+    features: Grid<Option<Cell>>,
+    /// When each cell blanks out, as a fraction of the way through the fade.
+    thresholds: Grid<f64>,
+    progress: f64,
+}
+
+impl FadeLayer {
+    pub fn new(rows: usize, cols: usize) -> Self {
+        let mut thresholds = Grid::new(rows, cols, 0.0);
+        for i in 0..rows {
+            for j in 0..cols {
+                thresholds[(i, j)] = noise::value_noise(j as f64 / FADE_SCALE, i as f64 / FADE_SCALE, 0.0);
+            }
+        }
+
+        FadeLayer {
+            features: Grid::new(rows, cols, None),
+            thresholds,
+            progress: 0.0,
+        }
+    }
+
+    pub fn set_progress(&mut self, progress: f64) {
+        self.progress = progress.clamp(0.0, 1.0);
+    }
+}
+
+impl Layerable for FadeLayer {
+    fn rows(&self) -> usize { self.features.rows() }
+    fn cols(&self) -> usize { self.features.cols() }
+    fn features(&self) -> &Grid<Option<Cell>> {
+        &self.features
+    }
+
+    fn blend(&self, row_ix: usize, col_ix: usize, below: Option<Cell>) -> Option<Cell> {
+        if self.progress >= 1.0 || self.thresholds[(row_ix, col_ix)] < self.progress {
+            return None;
+        }
+
+        below.map(|cell| Cell { fg: cell.fg.map(|fg| palette::mix(fg, Rgb(0, 0, 0), self.progress)), ..cell })
+    }
+}
+
+/// summary describes how the burn went, in a line, given its last `stats`, how many `ticks` it ran for and how big
+/// the page was.
+pub fn summary(stats: &Stats, ticks: u64, elapsed: Duration, rows: usize, cols: usize) -> String {
+    let cells = rows * cols;
+    let burned = stats.extinguished + stats.lit;
+
+    format!(
+        "burned {} of {} cells ({:.0}%) in {:.1}s, over {} ticks",
+        burned,
+        cells,
+        100.0 * burned as f64 / cells.max(1) as f64,
+        elapsed.as_secs_f64(),
+        ticks,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FadeLayer;
+    use layers::{Cell, Layerable};
+    use termion::color::Rgb;
+
+    #[test]
+    fn test_fade_darkens_then_blanks() {
+        let mut fade = FadeLayer::new(4, 4);
+        let cell = Some(Cell::colored('#', Rgb(200, 100, 0)));
+
+        assert_eq!(fade.blend(1, 1, cell), cell);
+
+        fade.set_progress(0.5);
+        let faded: Vec<Option<Cell>> = (0..4).flat_map(|i| (0..4).map(move |j| (i, j))).map(|(i, j)| fade.blend(i, j, cell)).collect();
+        assert!(faded.iter().flatten().all(|cell| cell.fg == Some(Rgb(100, 50, 0))));
+
+        fade.set_progress(1.0);
+        assert_eq!(fade.blend(1, 1, cell), None);
+    }
+}
@@ -0,0 +1,87 @@
+use std::time::Duration;
+
+use config::RenderingConfig;
+use scene::Scene;
+
+/// The most a tick may grow or shrink from one to the next while a burn is being kept to time. It's enough to make up
+/// for a guess at the length of the burn that was well off, over a few dozen ticks, without the burn visibly lurching.
+const MAX_CHANGE: f64 = 1.05;
+
+/// How long the ticks of a burn last. Either every tick lasts the same, or the burn is meant to take about a given
+/// time. How many ticks that will be isn't known until the burn is over, so the ticks are stretched or squeezed as it
+/// goes, to fit what's left of the burn, going by `Scene::ticks_left`, into what's left of the time.
+#[derive(Clone, Debug)]
+pub struct Pace {
+    duration: Option<Duration>,
+    tick: Duration,
+    /// How much of the burn's time the ticks so far have taken up.
+    elapsed: Duration,
+}
+
+impl Pace {
+    /// new paces `scene` so that it takes about `duration`, if one is given, and otherwise ticks every
+    /// `rendering.tick_ms`.
+    pub fn new(scene: &Scene, duration: Option<Duration>, rendering: &RenderingConfig) -> Self {
+        match duration {
+            Some(duration) => Pace {
+                duration: Some(duration),
+                tick: duration.div_f64((scene.ticks_left() + 1) as f64),
+                elapsed: Duration::ZERO,
+            },
+            None => Pace::every(Duration::from_millis(rendering.tick_ms)),
+        }
+    }
+
+    /// every paces a burn with ticks that all last `tick`.
+    pub fn every(tick: Duration) -> Self {
+        Pace { duration: None, tick, elapsed: Duration::ZERO }
+    }
+
+    /// tick is how long the next tick should last.
+    pub fn tick(&self) -> Duration {
+        self.tick
+    }
+
+    /// stepped accounts for a tick that `scene` has just been stepped by, and works out how long the next one should
+    /// last.
+    pub fn stepped(&mut self, scene: &Scene) {
+        self.elapsed += self.tick;
+
+        if let Some(duration) = self.duration {
+            let left = duration.saturating_sub(self.elapsed).as_secs_f64() / (scene.ticks_left() + 1) as f64;
+            let last = self.tick.as_secs_f64();
+
+            self.tick = Duration::from_secs_f64(left.clamp(last / MAX_CHANGE, last * MAX_CHANGE));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Pace;
+    use config::Config;
+    use scene::Scene;
+    use std::time::Duration;
+
+    #[test]
+    fn test_burn_takes_about_the_duration() {
+        let config = Config::default();
+        let mut scene = Scene::from_text("It was a bright cold day in April,\nand the clocks were striking thirteen.", 16, 40, &config, 3).unwrap();
+        let duration = Duration::from_secs(10);
+
+        let mut pace = Pace::new(&scene, Some(duration), &config.rendering);
+        let mut ticks = 0;
+
+        while !scene.is_over() {
+            scene.step();
+            pace.stepped(&scene);
+            ticks += 1;
+        }
+
+        let took = pace.elapsed.as_secs_f64();
+        assert!((8.5..=11.0).contains(&took), "took {:.2}s over {} ticks", took, ticks);
+
+        let fixed = Pace::new(&scene, None, &config.rendering);
+        assert_eq!(fixed.tick(), Duration::from_millis(config.rendering.tick_ms));
+    }
+}
@@ -0,0 +1,31 @@
+use termion::color::Rgb;
+
+/// Linearly interpolates between `from` and `to`; `t` is clamped to `0.0..=1.0`.
+pub fn mix(from: Rgb, to: Rgb, t: f64) -> Rgb {
+    let t = t.clamp(0.0, 1.0);
+    let channel = |a: u8, b: u8| (f64::from(a) + (f64::from(b) - f64::from(a)) * t).round() as u8;
+
+    Rgb(channel(from.0, to.0), channel(from.1, to.1), channel(from.2, to.2))
+}
+
+/// Picks the stop in `stops` that corresponds to `t` (clamped to `0.0..=1.0`),
+/// where `0.0` is the first stop and `1.0` the last.
+pub fn step<T: Copy>(stops: &[T], t: f64) -> T {
+    let t = t.clamp(0.0, 1.0);
+    let ix = (t * (stops.len() - 1) as f64).round() as usize;
+
+    stops[ix]
+}
+
+/// Like `step`, but blends smoothly between the two colors surrounding `t`.
+pub fn gradient(stops: &[Rgb], t: f64) -> Rgb {
+    let t = t.clamp(0.0, 1.0);
+    let scaled = t * (stops.len() - 1) as f64;
+    let lower = scaled.floor() as usize;
+
+    if lower + 1 >= stops.len() {
+        return stops[stops.len() - 1];
+    }
+
+    mix(stops[lower], stops[lower + 1], scaled - lower as f64)
+}
@@ -0,0 +1,277 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use backend::Backend;
+use color_depth::ColorDepth;
+use config::RenderingConfig;
+use grid::Grid;
+use layers::{Cell, Compositor};
+use outro::{FadeLayer, Outro};
+use pace::Pace;
+use scene::Scene;
+use scheduler::Scheduler;
+
+/// How often a paused burn checks whether it has been interrupted.
+const INTERRUPT_POLL: Duration = Duration::from_millis(20);
+
+/// Where the player gets the time from, and how it waits. Tests stand in a clock that only moves when it's slept on,
+/// so that they don't depend on how fast the machine running them is.
+trait Clock {
+    fn now(&self) -> Instant;
+    fn sleep(&self, duration: Duration);
+}
+
+/// The real thing.
+struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// Returned when a burn is stopped before it finishes, such as by SIGINT.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Interrupted;
+
+/// play burns `scene` through `backend`. The simulation ticks at `pace` whether or not frames keep up, unless stepping
+/// it takes longer than that, and frames are only drawn when there is something new to show, at most
+/// `rendering.fps` a second. Once the burn is over, or has gone on for `rendering.max_burn_ms`, the outro plays and
+/// `backend` is dropped, which for a terminal puts it back as it was. Returns how long the burn itself took, or
+/// `Interrupted` if `backend` was interrupted along the way. Failing to draw is taken as the terminal having gone away,
+/// which interrupts the burn too.
+///
+/// While the burn runs, the number keys show and hide the scene's layers: `1` the bottom one, `2` the one above it,
+/// and so on.
+pub fn play<B: Backend>(backend: B, scene: &mut Scene, pace: Pace, rendering: &RenderingConfig) -> Result<Duration, Interrupted> {
+    play_by(&SystemClock, backend, scene, pace, rendering)
+}
+
+/// play_by is `play`, going by `clock`.
+fn play_by<C: Clock, B: Backend>(clock: &C, mut backend: B, scene: &mut Scene, mut pace: Pace, rendering: &RenderingConfig) -> Result<Duration, Interrupted> {
+    // colors are left to the backend
+    let compositor = Compositor {
+        rows: scene.rows(),
+        cols: scene.cols(),
+        color_depth: ColorDepth::TrueColor,
+    };
+
+    // drawn into over and over, so that frames after the first don't allocate
+    let mut frame = Grid::new(compositor.rows, compositor.cols, Cell::plain(' '));
+
+    // the page as it's lit, before the first tick
+    scene.composite(|layers| compositor.composite_cells_into(layers, &mut frame));
+    backend.draw(&frame).map_err(|_| Interrupted)?;
+
+    // which takes the first frame's slot
+    let mut scheduler = Scheduler::new(pace.tick(), rendering.fps, clock.now());
+    scheduler.frame_due(clock.now());
+    let mut undrawn = false;
+    let started = clock.now();
+    let deadline = started + Duration::from_millis(rendering.max_burn_ms);
+
+    loop {
+        let now = clock.now();
+        // a burn that runs out of time ends where it is
+        let over = |scene: &Scene| scene.is_over() || now >= deadline;
+
+        if toggle_layers(&mut backend, scene) {
+            undrawn = true;
+        }
+
+        for _ in 0..scheduler.ticks_due(now) {
+            if over(scene) || scheduler.overran(now, clock.now()) {
+                break;
+            }
+
+            scene.step();
+            pace.stepped(scene);
+            scheduler.set_tick(pace.tick());
+            undrawn = true;
+        }
+
+        if undrawn && (over(scene) || scheduler.frame_due(now)) {
+            scene.composite(|layers| compositor.composite_cells_into(layers, &mut frame));
+            backend.draw(&frame).map_err(|_| Interrupted)?;
+            undrawn = false;
+        }
+
+        if over(scene) && !undrawn {
+            break;
+        }
+
+        pause(clock, &backend, scheduler.next_wake().saturating_duration_since(clock.now()))?;
+    }
+
+    let elapsed = clock.now() - started;
+    let outro = Duration::from_millis(rendering.outro_ms);
+
+    match rendering.outro {
+        Outro::None => {},
+        Outro::Hold | Outro::Summary => pause(clock, &backend, outro)?,
+        Outro::Fade => {
+            let mut fade = FadeLayer::new(compositor.rows, compositor.cols);
+            let frame_time = Duration::from_secs(1) / rendering.fps;
+            let fade_started = clock.now();
+
+            loop {
+                let progress = if outro.is_zero() { 1.0 } else { (clock.now() - fade_started).as_secs_f64() / outro.as_secs_f64() };
+                fade.set_progress(progress);
+
+                scene.composite(|layers| {
+                    let mut layers = layers.to_vec();
+                    layers.push(&fade);
+                    compositor.composite_cells_into(&layers, &mut frame)
+                });
+                backend.draw(&frame).map_err(|_| Interrupted)?;
+
+                if progress >= 1.0 {
+                    break;
+                }
+
+                pause(clock, &backend, frame_time)?;
+            }
+        },
+    }
+
+    Ok(elapsed)
+}
+
+/// toggle_layers shows or hides layers of `scene` for the number keys pressed on `backend` since it was last called,
+/// returning whether any were.
+fn toggle_layers<B: Backend>(backend: &mut B, scene: &mut Scene) -> bool {
+    let mut toggled = false;
+
+    while let Some(key) = backend.read_key() {
+        let name = key.to_digit(10)
+            .and_then(|n| n.checked_sub(1))
+            .and_then(|ix| scene.layer_names().nth(ix as usize))
+            .map(str::to_string);
+
+        if let Some(name) = name {
+            let visible = scene.is_visible(&name) == Some(true);
+            toggled |= scene.set_visible(&name, !visible).is_ok();
+        }
+    }
+
+    toggled
+}
+
+/// pause sleeps on `clock` for `duration`, keeping an eye out for `backend` being interrupted.
+fn pause<C: Clock, B: Backend>(clock: &C, backend: &B, duration: Duration) -> Result<(), Interrupted> {
+    let deadline = clock.now() + duration;
+
+    loop {
+        if backend.is_interrupted() {
+            return Err(Interrupted);
+        }
+
+        let left = deadline.saturating_duration_since(clock.now());
+        if left.is_zero() {
+            return Ok(());
+        }
+
+        clock.sleep(left.min(INTERRUPT_POLL));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{play, play_by, Clock};
+    use backend::MemoryBackend;
+    use config::Config;
+    use outro::Outro;
+    use pace::Pace;
+    use scene::Scene;
+    use std::cell::Cell;
+    use std::time::{Duration, Instant};
+
+    /// A clock that stands still until it's slept on.
+    struct FakeClock(Cell<Instant>);
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Instant {
+            self.0.get()
+        }
+
+        fn sleep(&self, duration: Duration) {
+            self.0.set(self.0.get() + duration);
+        }
+    }
+
+    #[test]
+    fn test_plays_to_the_end() {
+        let mut config = Config::default();
+        config.rendering.outro = Outro::Fade;
+        config.rendering.outro_ms = 0;
+
+        let mut scene = Scene::from_text("kindling", 6, 12, &config, 3).unwrap();
+        let mut backend = MemoryBackend::new();
+        play(&mut backend, &mut scene, Pace::every(Duration::from_micros(1)), &config.rendering).unwrap();
+
+        let frames = backend.frames();
+        let text = |row: &[_]| row.iter().map(|cell: &::layers::Cell| cell.glyph).collect::<String>();
+
+        assert!(scene.is_over());
+        assert!(frames.len() >= 2);
+        assert!(text(frames[0].row(1)).contains("kindling"));
+        assert!(frames.last().unwrap().iter().all(|cell| cell.glyph == ' '));
+    }
+
+    #[test]
+    fn test_short_duration_keeps_time() {
+        let mut config = Config::default();
+        config.rendering.outro = Outro::None;
+
+        // ticks come several to a frame, all of which have to run for the burn to take as long as asked
+        let mut scene = Scene::from_text("Dear diary,\ntoday I...", 20, 40, &config, 4).unwrap();
+        let duration = Duration::from_millis(400);
+        let pace = Pace::new(&scene, Some(duration), &config.rendering);
+        assert!(pace.tick() * config.rendering.fps < Duration::from_secs(1) / 8);
+
+        let clock = FakeClock(Cell::new(Instant::now()));
+        let elapsed = play_by(&clock, MemoryBackend::new(), &mut scene, pace, &config.rendering).unwrap();
+
+        assert!(scene.is_over());
+        assert!(elapsed > duration * 9 / 10 && elapsed < duration * 11 / 10, "took {:?}", elapsed);
+    }
+
+    #[test]
+    fn test_cut_short_after_max_burn_ms() {
+        let mut config = Config::default();
+        config.rendering.outro = Outro::None;
+        config.rendering.max_burn_ms = 100;
+
+        let mut scene = Scene::from_text("a long, slow burn", 20, 40, &config, 4).unwrap();
+        let clock = FakeClock(Cell::new(Instant::now()));
+        let elapsed = play_by(&clock, MemoryBackend::new(), &mut scene, Pace::every(Duration::from_millis(20)), &config.rendering).unwrap();
+
+        // the ticks due before time runs out are run, but not the one due just as it does
+        assert!(!scene.is_over());
+        assert_eq!(scene.ticks(), 4);
+        assert_eq!(elapsed, Duration::from_millis(100), "took {:?}", elapsed);
+    }
+
+    #[test]
+    fn test_number_keys_toggle_layers() {
+        let mut config = Config::default();
+        config.rendering.outro = Outro::None;
+
+        let mut scene = Scene::from_text("", 6, 12, &config, 3).unwrap();
+        let mut backend = MemoryBackend::new();
+        // hides the border, shows it, then hides it again, while keys that aren't for a layer are left alone
+        for key in "2x22 09".chars() {
+            backend.press(key);
+        }
+        play(&mut backend, &mut scene, Pace::every(Duration::from_micros(1)), &config.rendering).unwrap();
+
+        assert_eq!(scene.is_visible("border"), Some(false));
+        assert!(scene.layer_names().all(|name| scene.is_visible(name) == Some(name != "border")));
+        assert_ne!(backend.frames()[0][(0, 0)].glyph, ' ');
+        assert_eq!(backend.frames().last().unwrap()[(0, 0)].glyph, ' ');
+    }
+}
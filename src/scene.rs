@@ -0,0 +1,257 @@
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use border::Border;
+use color_depth::ColorDepth;
+use config::Config;
+use layers::{BasicLayer, Cell, Compositor, Layerable};
+use state::{CombustionState, Stats};
+use theme::Theme;
+
+/// Where a layer in a scene's stack is drawn from.
+#[derive(Clone)]
+enum Source {
+    Text,
+    Border,
+    Fire,
+    Ash,
+    Smoke,
+    /// One added with `Scene::add_layer`, such as a HUD or sprite.
+    Added(Rc<dyn Layerable>),
+}
+
+#[derive(Clone)]
+struct StackedLayer {
+    name: String,
+    source: Source,
+    visible: bool,
+}
+
+/// Everything drawn during a burn, as a stack of named layers. To begin with, these are, bottom first, the page of
+/// `text`, its `border`, and the `fire`, `ash` and `smoke` on top, but layers can be added, taken out, moved up and
+/// down the stack and hidden. Cloning a scene gives one that goes on to burn exactly the same way.
+#[derive(Clone)]
+pub struct Scene {
+    base_layer: BasicLayer,
+    border: Border,
+    state: CombustionState,
+    /// Bottom first.
+    stack: Vec<StackedLayer>,
+}
+
+impl Scene {
+    /// new lays `lines` out inside the border of a page `rows` by `cols` cells, border included, and lights the fire.
+    /// Burns with the same `seed` and settings play out identically. The page has to be at least 3 by 3.
+    pub fn new(lines: Vec<String>, rows: usize, cols: usize, theme: Theme, config: &Config, seed: u64) -> Result<Self, String> {
+        let border = Border::new(rows, cols, &theme.border)?;
+        let mut base_layer = BasicLayer::create(
+            rows - 2,
+            cols - 2,
+            lines.into_iter().map(|row| row.chars().map(|c| Some(Cell::plain(c))).collect()).collect(),
+        );
+        base_layer.set_origin(1, 1);
+
+        let mut state = CombustionState::new(rows, cols, &config.simulation, seed);
+
+        state.set_theme(Rc::new(theme));
+        state.set_translucent_smoke(config.rendering.translucent_smoke);
+        state.set_flicker(config.rendering.flicker);
+        state.start_fire();
+
+        let stack = vec![
+            ("text", Source::Text),
+            ("border", Source::Border),
+            ("fire", Source::Fire),
+            ("ash", Source::Ash),
+            ("smoke", Source::Smoke),
+        ];
+
+        Ok(Scene {
+            base_layer,
+            border,
+            state,
+            stack: stack.into_iter().map(|(name, source)| StackedLayer { name: name.to_string(), source, visible: true }).collect(),
+        })
+    }
+
+    /// from_text is like `new`, but takes the page as text, one line per row, and the theme named by `config`.
+    pub fn from_text(text: &str, rows: usize, cols: usize, config: &Config, seed: u64) -> Result<Self, String> {
+        let theme = Theme::load(&config.rendering.theme)?;
+        let lines = text.lines().take(rows.saturating_sub(2)).map(str::to_string).collect();
+
+        Self::new(lines, rows, cols, theme, config, seed)
+    }
+
+    pub fn rows(&self) -> usize {
+        self.border.rows()
+    }
+
+    pub fn cols(&self) -> usize {
+        self.border.cols()
+    }
+
+    /// step advances the simulation by one tick.
+    pub fn step(&mut self) {
+        self.state.step();
+    }
+
+    /// is_over is whether the fire has burned out and its smoke cleared, or the burn has been cut short.
+    pub fn is_over(&self) -> bool {
+        self.state.is_over()
+    }
+
+    /// ticks is how many times the scene has been stepped.
+    pub fn ticks(&self) -> u64 {
+        self.state.ticks()
+    }
+
+    /// ticks_left guesses how many more ticks the burn has to go; see `CombustionState::ticks_left`.
+    pub fn ticks_left(&self) -> u64 {
+        self.state.ticks_left()
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.state.stats()
+    }
+
+    /// layer_names lists the layers in the stack, bottom first, whether they're shown or not.
+    pub fn layer_names(&self) -> impl Iterator<Item = &str> {
+        self.stack.iter().map(|layer| layer.name.as_str())
+    }
+
+    /// add_layer puts `layer` on top of the stack, called `name`. If there's a layer called that already, `layer`
+    /// takes its place instead, shown or hidden as it was, which is how a layer that changes is kept up to date.
+    pub fn add_layer<L: Layerable + 'static>(&mut self, name: &str, layer: L) {
+        let source = Source::Added(Rc::new(layer));
+
+        match self.position(name) {
+            Ok(ix) => self.stack[ix].source = source,
+            Err(_) => self.stack.push(StackedLayer { name: name.to_string(), source, visible: true }),
+        }
+    }
+
+    /// remove_layer takes the layer called `name` out of the stack. The simulation goes on underneath whether its
+    /// layers are in the stack or not.
+    pub fn remove_layer(&mut self, name: &str) -> Result<(), String> {
+        let ix = self.position(name)?;
+        self.stack.remove(ix);
+
+        Ok(())
+    }
+
+    /// move_layer moves the layer called `name` to `position` in the stack, counting up from the bottom, or to the top
+    /// if the stack isn't that tall.
+    pub fn move_layer(&mut self, name: &str, position: usize) -> Result<(), String> {
+        let layer = self.stack.remove(self.position(name)?);
+        let position = position.min(self.stack.len());
+        self.stack.insert(position, layer);
+
+        Ok(())
+    }
+
+    /// is_visible is whether the layer called `name` is shown, or `None` if there's no such layer.
+    pub fn is_visible(&self, name: &str) -> Option<bool> {
+        self.position(name).ok().map(|ix| self.stack[ix].visible)
+    }
+
+    /// set_visible shows or hides the layer called `name`. Hidden layers stay in the stack, but aren't composited.
+    pub fn set_visible(&mut self, name: &str, visible: bool) -> Result<(), String> {
+        let ix = self.position(name)?;
+        self.stack[ix].visible = visible;
+
+        Ok(())
+    }
+
+    fn position(&self, name: &str) -> Result<usize, String> {
+        self.stack.iter().position(|layer| layer.name == name).ok_or_else(|| format!("no layer called `{}`", name))
+    }
+
+    /// composite hands the scene's visible layers, bottom first, to `f`; usually one of `Compositor`'s methods. The
+    /// layers are redrawn first if the simulation has moved on since they were last drawn.
+    pub fn composite<T, F: FnOnce(&[&dyn Layerable]) -> T>(&mut self, f: F) -> T {
+        self.state.render();
+
+        let layers: Vec<&dyn Layerable> = self.stack.iter()
+            .filter(|layer| layer.visible)
+            .map(|layer| match layer.source {
+                Source::Text => &self.base_layer as &dyn Layerable,
+                Source::Border => &self.border,
+                Source::Fire => self.state.fire_layer(),
+                Source::Ash => self.state.ash_layer(),
+                Source::Smoke => self.state.smoke_layer(),
+                Source::Added(ref layer) => &**layer,
+            })
+            .collect();
+
+        f(&layers)
+    }
+
+    /// write_frame draws the scene as it stands to `out`, one row to a line, in colors `color_depth` can show. Each line
+    /// ends in `\r\n`, so that it starts at the left margin even on a terminal in raw mode.
+    pub fn write_frame<W: Write>(&mut self, color_depth: ColorDepth, mut out: W) -> io::Result<()> {
+        let compositor = Compositor { rows: self.rows(), cols: self.cols(), color_depth };
+        let field = self.composite(|layers| compositor.composite(layers));
+
+        for line in field {
+            out.write_all(&line)?;
+            out.write_all(b"\r\n")?;
+        }
+
+        out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Scene;
+    use color_depth::ColorDepth;
+    use config::Config;
+    use layers::{BasicLayer, Cell};
+
+    fn glyphs(scene: &mut Scene) -> Vec<String> {
+        let mut frame = vec![];
+        scene.write_frame(ColorDepth::Monochrome, &mut frame).unwrap();
+
+        String::from_utf8(frame).unwrap().lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_layer_stack() {
+        let mut scene = Scene::from_text("ash", 4, 6, &Config::default(), 1).unwrap();
+        assert_eq!(scene.layer_names().collect::<Vec<_>>(), ["text", "border", "fire", "ash", "smoke"]);
+
+        let mut banner = BasicLayer::create(1, 2, vec![vec![Some(Cell::plain('!')); 2]]);
+        banner.set_origin(1, 2);
+        scene.add_layer("hud", banner);
+        scene.move_layer("hud", 1).unwrap();
+        scene.remove_layer("fire").unwrap();
+        assert_eq!(scene.layer_names().collect::<Vec<_>>(), ["text", "hud", "border", "ash", "smoke"]);
+
+        // the text is underneath the banner, and the border on top of both
+        assert_eq!(glyphs(&mut scene)[1], "┃a!! ┃");
+
+        scene.set_visible("hud", false).unwrap();
+        assert_eq!(scene.is_visible("hud"), Some(false));
+        assert_eq!(glyphs(&mut scene)[1], "┃ash ┃");
+
+        assert!(scene.set_visible("sparks", true).is_err());
+        assert_eq!(scene.is_visible("fire"), None);
+    }
+
+    #[test]
+    fn test_too_small_for_a_border() {
+        assert!(Scene::from_text("hi", 2, 10, &Config::default(), 1).is_err());
+        assert!(Scene::from_text("hi", 10, 0, &Config::default(), 1).is_err());
+        assert!(Scene::from_text("", 3, 3, &Config::default(), 1).is_ok());
+    }
+
+    #[test]
+    fn test_text_sits_inside_the_border() {
+        let mut scene = Scene::from_text("first\nsecond\nthird", 4, 10, &Config::default(), 1).unwrap();
+        let glyphs = glyphs(&mut scene);
+
+        assert_eq!(glyphs[1], "┃first   ┃");
+        assert_eq!(glyphs[2], "┃second  ┃");
+        assert!(glyphs[3].starts_with('┗'));
+    }
+}
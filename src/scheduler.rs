@@ -0,0 +1,116 @@
+use std::time::{Duration, Instant};
+
+/// How far behind the simulation may fall, in frames, before the ticks it missed are dropped. Between frames it falls a
+/// frame or so behind as a matter of course, so this only comes into play after a stall, such as the process being
+/// suspended, when catching up on every tick would hold up drawing for as long again.
+const MAX_CATCH_UP_FRAMES: u32 = 8;
+
+/// Paces a burn. The simulation advances in ticks, however often frames get drawn, and frames are drawn at most
+/// `fps` times a second: when ticks come faster than that, or drawing can't keep up, the frames in between are dropped.
+/// It never sleeps itself; it only says what is due at a given moment and when to wake up next.
+pub struct Scheduler {
+    tick: Duration,
+    frame: Duration,
+    /// When the simulation was last caught up to.
+    simulated_to: Instant,
+    next_frame: Instant,
+}
+
+impl Scheduler {
+    /// new starts pacing from `now`, with the first frame due straight away.
+    pub fn new(tick: Duration, fps: u32, now: Instant) -> Self {
+        Scheduler {
+            tick: tick.max(Duration::from_micros(1)),
+            frame: Duration::from_secs(1) / fps.max(1),
+            simulated_to: now,
+            next_frame: now,
+        }
+    }
+
+    /// set_tick changes how long ticks last from here on.
+    pub fn set_tick(&mut self, tick: Duration) {
+        self.tick = tick.max(Duration::from_micros(1));
+    }
+
+    /// ticks_due is how many ticks to simulate to catch up to `now`.
+    pub fn ticks_due(&mut self, now: Instant) -> u32 {
+        let behind = now.saturating_duration_since(self.simulated_to);
+        let ticks = behind.as_nanos() / self.tick.as_nanos();
+        let max_ticks = ((self.frame * MAX_CATCH_UP_FRAMES).as_nanos() / self.tick.as_nanos()).max(u128::from(MAX_CATCH_UP_FRAMES));
+
+        if ticks > max_ticks {
+            self.simulated_to = now;
+            return max_ticks.min(u128::from(u32::MAX)) as u32;
+        }
+
+        self.simulated_to += self.tick * ticks as u32;
+        ticks as u32
+    }
+
+    /// overran is whether simulating the ticks due, which began at `started`, has already taken up a frame's worth of
+    /// time by `now`. If so, the simulation can't keep up, and the rest of the ticks are better dropped than left to
+    /// hold up the next frame.
+    pub fn overran(&self, started: Instant, now: Instant) -> bool {
+        now.saturating_duration_since(started) >= self.frame
+    }
+
+    /// frame_due says whether a frame can be drawn at `now`, and if so, schedules the next one. Frames whose time has
+    /// passed are skipped rather than drawn in a rush.
+    pub fn frame_due(&mut self, now: Instant) -> bool {
+        if now < self.next_frame {
+            return false;
+        }
+
+        self.next_frame += self.frame;
+        if self.next_frame <= now {
+            self.next_frame = now + self.frame;
+        }
+
+        true
+    }
+
+    /// next_wake is when the next tick is due, or the next frame may be drawn if that's later.
+    pub fn next_wake(&self) -> Instant {
+        (self.simulated_to + self.tick).max(self.next_frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Scheduler, MAX_CATCH_UP_FRAMES};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_ticks_and_frames_are_paced() {
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+        let mut scheduler = Scheduler::new(ms(10), 20, start);
+
+        // ticks every 10ms, but frames only every 50ms
+        assert_eq!(scheduler.ticks_due(start), 0);
+        assert!(scheduler.frame_due(start));
+        assert_eq!(scheduler.ticks_due(start + ms(25)), 2);
+        assert!(!scheduler.frame_due(start + ms(25)));
+        assert_eq!(scheduler.next_wake(), start + ms(50));
+        assert_eq!(scheduler.ticks_due(start + ms(50)), 3);
+        assert!(scheduler.frame_due(start + ms(50)));
+
+        // after a long stall, only a few frames' worth of ticks are caught up and the missed frames are dropped
+        assert_eq!(scheduler.ticks_due(start + ms(1000)), MAX_CATCH_UP_FRAMES * 5);
+        assert!(scheduler.frame_due(start + ms(1000)));
+        assert!(!scheduler.frame_due(start + ms(1010)));
+        assert_eq!(scheduler.next_wake(), start + ms(1050));
+    }
+
+    #[test]
+    fn test_ticks_shorter_than_a_frame_all_run() {
+        let start = Instant::now();
+        let mut scheduler = Scheduler::new(Duration::from_micros(500), 60, start);
+
+        // a frame's sleep is about 33 ticks, all of which are owed
+        assert!(scheduler.frame_due(start));
+        assert_eq!(scheduler.ticks_due(scheduler.next_wake()), 33);
+        assert!(!scheduler.overran(start, start + Duration::from_millis(16)));
+        assert!(scheduler.overran(start, start + Duration::from_millis(17)));
+    }
+}
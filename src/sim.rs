@@ -0,0 +1,102 @@
+use std::io::{self, Write};
+
+use color_depth::ColorDepth;
+use grid::Grid;
+use layers::{Cell, Compositor};
+use scene::Scene;
+use state::Stats;
+
+/// How `burn sim` writes its statistics.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum StatsFormat {
+    /// An array of one object per frame, one frame to a line.
+    Json,
+    /// A header row, then one row per frame.
+    Csv,
+}
+
+/// run steps `scene` with no terminal, collecting stats for every frame that would have been drawn: `frames` of them,
+/// or until the burn is over if no count is given. The first frame is the page just after the fire was lit.
+pub fn run(scene: &mut Scene, frames: Option<usize>) -> Vec<Stats> {
+    let mut stats = vec![scene.stats()];
+
+    loop {
+        let done = match frames {
+            Some(frames) => stats.len() >= frames,
+            None => scene.is_over(),
+        };
+
+        if done {
+            return stats;
+        }
+
+        scene.step();
+        stats.push(scene.stats());
+    }
+}
+
+pub fn write_stats<W: Write>(stats: &[Stats], format: StatsFormat, mut out: W) -> io::Result<()> {
+    match format {
+        StatsFormat::Json => {
+            writeln!(out, "[")?;
+            for (frame, frame_stats) in stats.iter().enumerate() {
+                let front = frame_stats.front.map_or("null".to_string(), |row| row.to_string());
+                let separator = if frame + 1 < stats.len() { "," } else { "" };
+
+                writeln!(
+                    out,
+                    "  {{\"frame\": {}, \"lit\": {}, \"extinguished\": {}, \"smoke\": {:.3}, \"front\": {}}}{}",
+                    frame, frame_stats.lit, frame_stats.extinguished, frame_stats.smoke, front, separator,
+                )?;
+            }
+            writeln!(out, "]")?;
+        },
+        StatsFormat::Csv => {
+            writeln!(out, "frame,lit,extinguished,smoke,front")?;
+            for (frame, frame_stats) in stats.iter().enumerate() {
+                let front = frame_stats.front.map_or(String::new(), |row| row.to_string());
+
+                writeln!(out, "{},{},{},{:.3},{}", frame, frame_stats.lit, frame_stats.extinguished, frame_stats.smoke, front)?;
+            }
+        },
+    }
+
+    out.flush()
+}
+
+/// plain_text draws the scene, which is `rows` by `cols` cells, as text without any color. The cells are composited
+/// into `frame` first, so that drawing frame after frame can reuse it.
+pub fn plain_text(scene: &mut Scene, rows: usize, cols: usize, frame: &mut Grid<Cell>) -> String {
+    let compositor = Compositor { rows, cols, color_depth: ColorDepth::Monochrome };
+    scene.composite(|layers| compositor.composite_cells_into(layers, frame));
+
+    frame.iter_rows()
+        .map(|row| row.iter().map(|cell| cell.glyph).collect::<String>().trim_end().to_string() + "\n")
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_stats, StatsFormat};
+    use state::Stats;
+
+    #[test]
+    fn test_write_stats() {
+        let stats = [
+            Stats { lit: 1, extinguished: 0, smoke: 0.0, front: Some(21) },
+            Stats { lit: 0, extinguished: 30, smoke: 2.5, front: None },
+        ];
+
+        let mut csv = vec![];
+        write_stats(&stats, StatsFormat::Csv, &mut csv).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap(), "frame,lit,extinguished,smoke,front\n0,1,0,0.000,21\n1,0,30,2.500,\n");
+
+        let mut json = vec![];
+        write_stats(&stats, StatsFormat::Json, &mut json).unwrap();
+        assert_eq!(
+            String::from_utf8(json).unwrap(),
+            "[\n  {\"frame\": 0, \"lit\": 1, \"extinguished\": 0, \"smoke\": 0.000, \"front\": 21},\n  \
+             {\"frame\": 1, \"lit\": 0, \"extinguished\": 30, \"smoke\": 2.500, \"front\": null}\n]\n",
+        );
+    }
+}
@@ -0,0 +1,209 @@
+use grid::Grid;
+use layers::{Cell, Layerable};
+use serde::{Deserialize, Serialize};
+use theme::Theme;
+
+use super::rng::SimRng;
+use super::fire_state::{FireCell, FireState};
+
+/// Tunables for falling ash.
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AshConfig {
+    /// Each cold ember has a 1-in-`crumble_odds` chance of leaving a particle of ash behind.
+    /// Much lower than the default and the pile buries the page.
+    pub crumble_odds: u32,
+}
+
+impl Default for AshConfig {
+    fn default() -> Self {
+        AshConfig { crumble_odds: 5 }
+    }
+}
+
+impl AshConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.crumble_odds == 0 {
+            return Err("crumble_odds should be at least 1".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone)]
+enum AshCell {
+    Clear,
+    /// `glyph` and `shade` pick a glyph and color from the theme's ash ramp.
+    Ash {
+        glyph: u8,
+        shade: u8,
+    },
+}
+
+impl AshCell {
+    fn render(self, theme: &Theme) -> Option<Cell> {
+        match self {
+            AshCell::Clear => None,
+            AshCell::Ash { glyph, shade } => Some(theme.ash.pick(glyph as usize, shade as usize)),
+        }
+    }
+}
+
+/// Particles of ash left behind once burned-out cells have cooled. Ash falls under gravity, slides off of the top of
+/// piles like sand, and rests on the bottom of the `Border` or on any paper that hasn't burned yet.
+/// Wind nudges particles sideways, whether they're falling or already on a pile.
+#[derive(Clone)]
+pub struct AshState {
+    rows: usize,
+    cols: usize,
+    /// How many cells in from the edges of the grid the walls and floor begin.
+    inset: usize,
+    features: Grid<AshCell>,
+    config: AshConfig,
+}
+
+impl AshState {
+    pub fn new(rows: usize, cols: usize, config: AshConfig) -> Self {
+        Self {
+            rows,
+            cols,
+            inset: 1,
+            features: Grid::new(rows, cols, AshCell::Clear),
+            config,
+        }
+    }
+
+    /// Keeps ash `inset` cells in from the edges of the grid, rather than inside the one-cell ring of a `Border`.
+    pub fn set_inset(&mut self, inset: usize) {
+        self.inset = inset;
+    }
+
+    /// is_open reports whether ash can move into (`row`, `col`): it must lie inside the walls, be free of
+    /// other ash, and the paper there must have started burning.
+    fn is_open(&self, fire_state: &FireState, row: isize, col: isize) -> bool {
+        let inset = self.inset as isize;
+        if row < inset || col < inset || row + inset >= self.rows as isize || col + inset >= self.cols as isize {
+            return false;
+        }
+
+        let (row, col) = (row as usize, col as usize);
+
+        match (self.features[(row, col)], fire_state.features[(row, col)]) {
+            (AshCell::Clear, FireCell::Unlit) | (AshCell::Clear, FireCell::Scorched { .. }) => false,
+            (AshCell::Clear, _) => true,
+            _ => false,
+        }
+    }
+
+    fn move_ash(&mut self, from: (usize, usize), to: (isize, isize)) {
+        let (to_row, to_col) = (to.0 as usize, to.1 as usize);
+
+        self.features[(to_row, to_col)] = self.features[from];
+        self.features[from] = AshCell::Clear;
+    }
+
+    /// step lets every particle fall or blow one cell, then crumbles any embers that went cold in the tick `fire` just
+    /// took into ash. Particles are moved in place, so the order they're visited in matters.
+    pub fn step(&mut self, fire: &FireState, wind: f64, rng: &mut SimRng) {
+        // work from the bottom up so that a particle never moves twice in one tick, and against the wind for the same reason
+        let wind_step = if wind < 0.0 { -1 } else { 1 };
+
+        for i in (0..self.rows).rev() {
+            for n in 0..self.cols {
+                let j = if wind > 0.0 { self.cols - 1 - n } else { n };
+
+                if let AshCell::Clear = self.features[(i, j)] {
+                    continue;
+                }
+
+                let (row, col) = (i as isize, j as isize);
+                let blown = wind != 0.0 && rng.next_f64() < wind.abs();
+
+                let target = if blown && self.is_open(fire, row, col + wind_step) {
+                    Some((row, col + wind_step))
+                } else if self.is_open(fire, row + 1, col) {
+                    Some((row + 1, col))
+                } else {
+                    let slide = if rng.coin() { -1 } else { 1 };
+
+                    [slide, -slide].iter()
+                        .map(|&d_col| (row + 1, col + d_col))
+                        .find(|&(row, col)| self.is_open(fire, row, col))
+                };
+
+                if let Some(target) = target {
+                    self.move_ash((i, j), target);
+                }
+            }
+        }
+
+        for i in 0..self.rows {
+            for j in 0..self.cols {
+                let burned_out = matches!(
+                    (fire.previous()[(i, j)], fire.features[(i, j)]),
+                    (FireCell::Extinguished { temperature }, FireCell::Extinguished { temperature: 0 }) if temperature > 0
+                );
+
+                if burned_out && rng.one_in(self.config.crumble_odds) && self.is_open(fire, i as isize, j as isize) {
+                    self.features[(i, j)] = AshCell::Ash {
+                        glyph: rng.next_u64() as u8,
+                        shade: rng.next_u64() as u8,
+                    };
+                }
+            }
+        }
+    }
+
+    /// render draws the ash into `layer`.
+    pub fn render(&self, theme: &Theme, layer: &mut AshLayer) {
+        for (cell, feature) in self.features.iter().zip(layer.features.iter_mut()) {
+            *feature = cell.render(theme);
+        }
+    }
+}
+
+/// The ash as drawn: a glyph for every particle still falling or settled on the page.
+#[derive(Clone)]
+pub struct AshLayer {
+    features: Grid<Option<Cell>>,
+}
+
+impl AshLayer {
+    pub fn new(rows: usize, cols: usize) -> Self {
+        AshLayer { features: Grid::new(rows, cols, None) }
+    }
+}
+
+impl Layerable for AshLayer {
+    fn rows(&self) -> usize { self.features.rows() }
+    fn cols(&self) -> usize { self.features.cols() }
+    fn features(&self) -> &Grid<Option<Cell>> { &self.features }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AshCell, AshConfig, AshState};
+    use state::{FireConfig, FireState, SimRng};
+    use state::fire_state::FireCell;
+
+    #[test]
+    fn test_ash_piles_on_floor() {
+        let (rows, cols) = (6, 3);
+        let mut fire = FireState::new(rows, cols, FireConfig::default());
+        fire.features.fill(FireCell::Extinguished { temperature: 0 });
+
+        let mut ash = AshState::new(rows, cols, AshConfig::default());
+        ash.features[(1, 1)] = AshCell::Ash { glyph: 0, shade: 0 };
+        ash.features[(2, 1)] = AshCell::Ash { glyph: 1, shade: 0 };
+
+        let mut rng = SimRng::new(0);
+        for _ in 0..rows {
+            ash.step(&fire, 0.0, &mut rng);
+        }
+
+        // the floor is the row just above the bottom of the border
+        let occupied: Vec<bool> = ash.features.iter_rows().map(|row| matches!(row[1], AshCell::Ash { .. })).collect();
+        assert_eq!(occupied, vec![false, false, false, true, true, false]);
+    }
+}
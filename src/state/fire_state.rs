@@ -1,65 +1,120 @@
-use rand::{self, Rng};
-use rand::distributions::IndependentSample;
-use layers::Layerable;
+use std::mem;
+
+use grid::Grid;
+use layers::{Cell, Layerable};
+use noise;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use termion::color;
+use theme::Theme;
+
+use super::BAND_ROWS;
+use super::rng::SimRng;
+
+/// A lit cell with at least this much `ttl` left burns at full intensity.
+const PEAK_TTL: usize = 14;
+
+/// How far flicker can push a flame's intensity up or down.
+const FLICKER_AMPLITUDE: f64 = 0.3;
+
+/// Size, in cells, of the patches that flicker together.
+const FLICKER_SCALE: f64 = 3.0;
+
+/// Number of ticks it takes the flicker pattern to change completely.
+const FLICKER_PERIOD: f64 = 2.0;
+
+/// Tunables for how fire spreads and burns out.
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FireConfig {
+    /// A cell that catches fire burns for between `ttl_min` and `ttl_max` ticks, inclusive.
+    pub ttl_min: usize,
+    pub ttl_max: usize,
+    /// Chance per burning neighbor per tick that a cell at `max_heat` catches fire. Cooler cells scale this down.
+    pub catch_chance: f64,
+    /// Heat at which a scorched cell is as likely to catch as it will ever be.
+    pub max_heat: u8,
+    /// Heat a cell gains per tick for each burning neighbor.
+    pub heat_per_fire: u8,
+    /// Scorched cells at least this hot slowly warm their neighbors too, pushing the char front out ahead of the flames.
+    pub creep_heat: u8,
+    /// Each tick, a cell next to one that is at least `creep_heat` has a 1-in-`creep_odds` chance of warming up.
+    pub creep_odds: u32,
+    /// Temperature of an ember when its flame first goes out. It loses one degree per tick.
+    pub ember_temperature: u8,
+    /// Each tick, a glowing ember has a 1-in-`reflare_odds` chance of flaring back up by `reflare_heat`.
+    pub reflare_odds: u32,
+    pub reflare_heat: u8,
+}
+
+impl Default for FireConfig {
+    fn default() -> Self {
+        FireConfig {
+            ttl_min: 3,
+            ttl_max: 25,
+            catch_chance: 1.0 / 3.0,
+            max_heat: 10,
+            heat_per_fire: 2,
+            creep_heat: 4,
+            creep_odds: 3,
+            ember_temperature: 12,
+            reflare_odds: 40,
+            reflare_heat: 6,
+        }
+    }
+}
 
-const FIRE_GLYPHS: &[char] = &[
-    '\x25', // %
-    '\x2A', // *
-    '\x2C', // ,
-    'W',
-    'Y',
-    '^',
-];
-
-const ASH_GLYPHS: &[char] = &[
-    '.',
-    ' ',
-    ' ',
-    ' ',
-    ' ',
-    ' ',
-    ' ',
-];
-
-const FIRE_COLORS: &[color::Rgb] = &[
-    color::Rgb(232, 81, 44),
-    color::Rgb(198, 46, 7),
-    color::Rgb(247, 39, 67),
-    color::Rgb(255, 134, 5),
-    color::Rgb(255, 72, 48),
-];
+impl FireConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.ttl_min > self.ttl_max {
+            return Err("ttl_min should not be greater than ttl_max".to_string());
+        }
+        if self.max_heat == 0 || self.ember_temperature == 0 {
+            return Err("max_heat and ember_temperature should be at least 1".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.catch_chance) {
+            return Err("catch_chance should be between 0 and 1".to_string());
+        }
+
+        Ok(())
+    }
+}
 
 #[derive(Copy, Clone)]
 pub enum FireCell {
     Unlit,
+    /// Heating up before catching: the text browns and then darkens as `heat` approaches `FireConfig::max_heat`.
+    Scorched { heat: u8 },
     Lit { ttl: usize },
-    Extinguished { glyph: char },
+    /// Burned out. The cell glows as an ember while it cools; once `temperature` reaches zero its ash
+    /// crumbles away (see `AshState`), leaving a hole in the page.
+    Extinguished { temperature: u8 },
 }
 
-impl From<FireCell> for Option<String> {
-    fn from(fire_cell: FireCell) -> Self {
-        use self::FireCell::{Unlit, Lit, Extinguished};
+impl FireCell {
+    /// render draws the cell. `flicker` is added to a flame's intensity, which otherwise falls off with its `ttl`.
+    fn render(self, theme: &Theme, config: &FireConfig, flicker: f64) -> Option<Cell> {
+        use self::FireCell::{Unlit, Scorched, Lit, Extinguished};
 
-        let mut rng = rand::thread_rng();
+        match self {
+            // scorching recolors whatever is beneath it; see `FireLayer::blend`
+            Unlit | Scorched { .. } => None,
+            Lit { ttl } => {
+                let intensity = ttl.min(PEAK_TTL) as f64 / PEAK_TTL as f64 + flicker;
 
-        match fire_cell {
-            Unlit => None,
-            Lit {..} => {
-                let glyph = *(rng.choose(FIRE_GLYPHS).unwrap());
-                let fire_color = *(rng.choose(FIRE_COLORS).unwrap());
+                Some(theme.fire.cell_at(intensity))
+            },
+            Extinguished { temperature: 0 } => Some(Cell::plain(' ')),
+            Extinguished { temperature } => {
+                let t = f64::from(temperature) / f64::from(config.ember_temperature);
 
-                Some(format!("{}{}{}", color::Fg(fire_color), glyph, color::Fg(color::Reset)))
+                Some(theme.embers.cell_at(t))
             },
-            Extinguished { glyph } => Some(format!("{}{}{}", color::Fg(color::Rgb(100, 100, 100)), glyph, color::Fg(color::Reset))),
         }
     }
 }
 
 struct Neighbors {
-    row: usize,
-    col: usize,
-
     top: FireCell,
     right: FireCell,
     bottom: FireCell,
@@ -67,12 +122,28 @@ struct Neighbors {
 }
 
 impl Neighbors {
+    fn of(features: &Grid<FireCell>, row: usize, col: usize) -> Self {
+        use self::FireCell::Unlit;
+
+        Neighbors {
+            // no consequence for modeling 'out-of-bounds' neighbors as `Unlit`;
+            // we just need to know if there are any real neighbors on fire
+            top: if row == 0 { Unlit } else { features[(row - 1, col)] },
+            right: if col + 1 == features.cols() { Unlit } else { features[(row, col + 1)] },
+            bottom: if row + 1 == features.rows() { Unlit } else { features[(row + 1, col)] },
+            left: if col == 0 { Unlit } else { features[(row, col - 1)] },
+        }
+    }
+
     fn n_fires(&self) -> usize {
-        [self.top, self.right, self.bottom, self.left].into_iter().filter(|&&cell| if let FireCell::Lit {..} = cell { true } else { false }).count()
+        [self.top, self.right, self.bottom, self.left].iter().filter(|&&cell| matches!(cell, FireCell::Lit {..})).count()
     }
 
-    fn fire_in_neighborhood(&self) -> bool {
-        self.n_fires() > 0
+    fn max_heat(&self) -> u8 {
+        [self.top, self.right, self.bottom, self.left].iter()
+            .map(|&cell| if let FireCell::Scorched { heat } = cell { heat } else { 0 })
+            .max()
+            .unwrap_or(0)
     }
 }
 
@@ -80,145 +151,283 @@ impl Neighbors {
 pub struct FireState {
     rows: usize,
     cols: usize,
-    pub features: Vec<Vec<FireCell>>, // needs to be public for calculating smoke layer. maybe this can be refined
-    n_fires: usize,
-    ttl_range: rand::distributions::Range<usize>,
+    pub features: Grid<FireCell>, // needs to be public for calculating smoke layer. maybe this can be refined
+    /// Where the next tick is written before it's swapped into `features`.
+    next: Grid<FireCell>,
+    /// How many cells were on fire as of the last tick.
+    n_lit: usize,
+    tick: u64,
+    config: FireConfig,
 }
 
 impl FireState {
-    pub fn new(rows: usize, cols: usize) -> Self {
-        let features = vec![vec![FireCell::Unlit; cols]; rows];
+    pub fn new(rows: usize, cols: usize, config: FireConfig) -> Self {
+        let features = Grid::new(rows, cols, FireCell::Unlit);
 
         Self {
             rows,
             cols,
+            next: features.clone(),
             features,
-            n_fires: 0,
-            ttl_range: rand::distributions::Range::new(3, 26),
+            n_lit: 0,
+            tick: 0,
+            config,
         }
     }
 
-    fn set_cell_fire(&mut self, row: usize, col: usize) {
-        // TODO: tweak/iterate on ttl, possibly extract into constant for maintenance
-        let ttl = self.ttl_range.ind_sample(&mut rand::thread_rng());
-        self.features[row][col] = FireCell::Lit { ttl };
-        self.n_fires += 1;
+    fn new_fire(&self, rng: &mut SimRng) -> FireCell {
+        FireCell::Lit { ttl: self.config.ttl_min + rng.below(self.config.ttl_max - self.config.ttl_min + 1) }
     }
 
-    pub fn start_fire(&mut self) {
+    pub fn start_fire(&mut self, rng: &mut SimRng) {
         let row_ix = self.rows - 1;
-        // TODO: replace with a call to thread-local RNG's `gen_range` method. Probably more efficient
-        let col_ix: usize = rand::random::<usize>() % self.cols;
-
-        self.set_cell_fire(row_ix, col_ix);
-    }
-
-    pub fn get_next(&self) -> Self {
-        let mut next = self.clone();
-
-        for (i, row) in (&self.features).into_iter().enumerate() {
-            for (j, cell) in row.into_iter().enumerate() {
-                use self::FireCell::{Unlit, Lit, Extinguished};
-
-                // TODO: return *next state for cell* from this match and assign to `next[i][j]` only once instead of burying the mutations in branches
-                match *cell {
-                    // give cell the opportunity to combust; may not due to randomness
-                    Unlit => {
-                        let neighbors = self.get_neighbors(i, j);
-
-                        let mut tries = neighbors.n_fires();
-                        let mut should_combust = false;
-
-                        while tries > 0 && !should_combust {
-                            tries -= 1;
-                            should_combust = rand::thread_rng().gen_weighted_bool(7);
-                        }
-
-                        if should_combust { next.set_cell_fire(i, j) }
-                    },
-                    Lit { ttl } => {
-                        if ttl < 1 {
-                            let glyph = *(rand::thread_rng().choose(ASH_GLYPHS).unwrap());
-                            next.features[i][j] = Extinguished { glyph };
-                        } else {
-                            next.features[i][j] = Lit { ttl: ttl - 1 };
-                        }
-                    },
-                    _ => {},
+        let col_ix = rng.below(self.cols);
+
+        self.features[(row_ix, col_ix)] = self.new_fire(rng);
+        self.n_lit += 1;
+    }
+
+    /// step advances the fire by one tick, a band of rows at a time in parallel. Each band draws from its own stream
+    /// of random numbers, seeded from `rng`.
+    pub fn step(&mut self, rng: &mut SimRng) {
+        let seed = rng.next_u64();
+        let mut next = mem::replace(&mut self.next, Grid::new(0, 0, FireCell::Unlit));
+
+        let lit: usize = next.par_bands_mut(BAND_ROWS).enumerate().map(|(band, cells)| {
+            let mut rng = SimRng::stream(seed, band as u64);
+            let mut lit = 0;
+
+            for (ix, next_cell) in cells.iter_mut().enumerate() {
+                let (i, j) = (band * BAND_ROWS + ix / self.cols, ix % self.cols);
+                let cell = self.features[(i, j)];
+
+                *next_cell = self.next_cell(cell, i, j, &mut rng);
+
+                if matches!(*next_cell, FireCell::Lit { .. }) {
+                    lit += 1;
                 }
             }
-        }
 
-        next
+            lit
+        }).sum();
+
+        self.n_lit = lit;
+        self.next = next;
+
+        mem::swap(&mut self.features, &mut self.next);
+        self.tick += 1;
     }
 
-    fn get_neighbors(&self, row: usize, col: usize) -> Neighbors {
-        use self::FireCell::Unlit;
+    /// next_cell works out what `cell`, at (`row`, `col`), becomes on the next tick.
+    fn next_cell(&self, cell: FireCell, row: usize, col: usize, rng: &mut SimRng) -> FireCell {
+        use self::FireCell::{Unlit, Scorched, Lit, Extinguished};
 
-        Neighbors {
-            row,
-            col,
-            // no consequence for modeling 'out-of-bounds' neighbors as `Unlit`;
-            // we just need to know if there are any real neighbors on fire
-            top: if row == 0 { Unlit } else { self.features[row - 1][col] },
-            right: if col + 1 == self.cols { Unlit } else { self.features[row][col + 1] },
-            bottom: if row + 1 == self.rows { Unlit } else { self.features[row + 1][col] },
-            left: if col == 0 { Unlit } else { self.features[row][col - 1] },
+        match cell {
+            // heat the cell up, and give it the opportunity to combust; may not due to randomness
+            Unlit | Scorched { .. } => {
+                let heat = if let Scorched { heat } = cell { heat } else { 0 };
+                let neighbors = Neighbors::of(&self.features, row, col);
+
+                let catch_chance = self.config.catch_chance * f64::from(heat) / f64::from(self.config.max_heat);
+                let mut tries = neighbors.n_fires();
+                let mut should_combust = false;
+
+                while tries > 0 && !should_combust {
+                    tries -= 1;
+                    should_combust = rng.next_f64() < catch_chance;
+                }
+
+                if should_combust {
+                    return self.new_fire(rng);
+                }
+
+                let mut gained = self.config.heat_per_fire.saturating_mul(neighbors.n_fires() as u8);
+                if neighbors.max_heat() >= self.config.creep_heat && rng.one_in(self.config.creep_odds) {
+                    gained = gained.saturating_add(1);
+                }
+
+                if gained > 0 {
+                    Scorched { heat: heat.saturating_add(gained).min(self.config.max_heat) }
+                } else {
+                    cell
+                }
+            },
+            Lit { ttl: 0 } => Extinguished { temperature: self.config.ember_temperature },
+            Lit { ttl } => Lit { ttl: ttl - 1 },
+            Extinguished { temperature: 0 } => cell,
+            // embers cool steadily, but now and then flare back up a little
+            Extinguished { temperature } => {
+                let temperature = if rng.one_in(self.config.reflare_odds) {
+                    temperature.saturating_add(self.config.reflare_heat).min(self.config.ember_temperature)
+                } else {
+                    temperature - 1
+                };
+
+                Extinguished { temperature }
+            },
         }
     }
 
-    pub fn is_saturated(&self) -> bool {
-        (self.n_fires as f64 / (self.rows * self.cols) as f64) > 0.99f64
+    /// previous is how the fire looked a tick ago.
+    pub fn previous(&self) -> &Grid<FireCell> {
+        &self.next
+    }
+
+    /// count is how many cells `matches` picks out.
+    pub fn count<F: Fn(FireCell) -> bool>(&self, matches: F) -> usize {
+        self.features.iter().filter(|&&cell| matches(cell)).count()
+    }
+
+    /// is_burning is whether any cell is still on fire. Once none are, nothing can catch again.
+    pub fn is_burning(&self) -> bool {
+        self.n_lit > 0
+    }
+
+    /// ticks_left guesses how much longer the fire will burn, given that it was lit `ticks` ago: long enough to reach
+    /// the rest of the page as quickly as it has reached the part it has so far, and for the last cells it reaches to
+    /// burn out.
+    pub fn ticks_left(&self, ticks: u64) -> u64 {
+        if !self.is_burning() {
+            return 0;
+        }
+
+        let reached = self.count(|cell| matches!(cell, FireCell::Lit { .. } | FireCell::Extinguished { .. })) as u64;
+        let unreached = (self.rows * self.cols) as u64 - reached;
+        let average_ttl = (self.config.ttl_min + self.config.ttl_max) as u64 / 2;
+
+        // the cells lit to begin with count as a tick's worth of spreading
+        (ticks + 1) * unreached / reached.max(1) + average_ttl
     }
 
-    pub fn as_layer(&self) -> FireLayer {
-        self.into()
+    /// render draws the fire into `layer`. When `flicker` is set, flames brighten and dim in patches that drift over
+    /// time.
+    pub fn render(&self, theme: &Theme, flicker: bool, layer: &mut FireLayer) {
+        for i in 0..self.rows {
+            for j in 0..self.cols {
+                let cell = self.features[(i, j)];
+                let flicker = if flicker && matches!(cell, FireCell::Lit { .. }) {
+                    let sample = noise::value_noise(i as f64 / FLICKER_SCALE, j as f64 / FLICKER_SCALE, self.tick as f64 / FLICKER_PERIOD);
+                    FLICKER_AMPLITUDE * (2.0 * sample - 1.0)
+                } else {
+                    0.0
+                };
+
+                layer.features[(i, j)] = cell.render(theme, &self.config, flicker);
+                layer.scorch[(i, j)] = match cell {
+                    FireCell::Scorched { heat } => theme.scorch.color_at(f64::from(heat) / f64::from(self.config.max_heat)),
+                    _ => None,
+                };
+            }
+        }
     }
 }
 
+/// The fire as drawn, along with the scorch each cell has taken, which tints whatever is beneath it rather than
+/// covering it.
+#[derive(Clone)]
 pub struct FireLayer {
-    rows: usize,
-    cols: usize,
-    features: Vec<Vec<Option<String>>>,
+    features: Grid<Option<Cell>>,
+    scorch: Grid<Option<color::Rgb>>,
 }
 
-impl Layerable for FireLayer {
-    fn rows(&self) -> usize { self.rows }
-    fn cols(&self) -> usize { self.cols }
-    fn features(&self) -> &Vec<Vec<Option<String>>> { &self.features }
+impl FireLayer {
+    pub fn new(rows: usize, cols: usize) -> Self {
+        FireLayer {
+            features: Grid::new(rows, cols, None),
+            scorch: Grid::new(rows, cols, None),
+        }
+    }
 }
 
-impl<'a> From<&'a FireState> for FireLayer {
-    fn from(fire_state: &'a FireState) -> Self {
-        let features: Vec<Vec<Option<String>>> = (&fire_state.features).into_iter()
-            .map(|row| {
-                row.into_iter().map(|&cell| cell.into()).collect::<Vec<Option<String>>>()
-            })
-            .collect::<Vec<_>>();
-
-        FireLayer {
-            features,
-            rows: fire_state.rows,
-            cols: fire_state.cols,
+impl Layerable for FireLayer {
+    fn rows(&self) -> usize { self.features.rows() }
+    fn cols(&self) -> usize { self.features.cols() }
+    fn features(&self) -> &Grid<Option<Cell>> { &self.features }
+
+    fn blend(&self, row_ix: usize, col_ix: usize, below: Option<Cell>) -> Option<Cell> {
+        match (self.scorch[(row_ix, col_ix)], below) {
+            (Some(char_color), Some(under)) => Some(Cell::colored(under.glyph, char_color)),
+            _ => self.get(row_ix, col_ix).or(below),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::FireState;
-    use super::FireCell;
+    use super::{FireCell, FireConfig, FireState};
+    use state::SimRng;
+    use theme::Theme;
 
     #[test]
     fn test_start_fire() {
-        let mut fire_state = FireState::new(3, 3);
+        let mut fire_state = FireState::new(3, 3, FireConfig::default());
 
-        fire_state.start_fire();
+        fire_state.start_fire(&mut SimRng::new(0));
 
-        let last_row: &mut Vec<FireCell> = (&mut fire_state.features).into_iter().last().unwrap();
+        let last_row: &[FireCell] = fire_state.features.row(2);
 
-        let fire_cell_count = last_row.into_iter().fold(0, |acc, &mut cell| match cell { Some(_) => acc + 1, None => acc });
+        let fire_cell_count = last_row.iter().filter(|&&cell| matches!(cell, FireCell::Lit { .. })).count();
 
         assert_eq!(fire_cell_count, 1);
     }
+
+    #[test]
+    fn test_cells_scorch_before_catching() {
+        let mut fire_state = FireState::new(1, 3, FireConfig::default());
+        fire_state.features[(0, 1)] = FireCell::Lit { ttl: 10 };
+
+        fire_state.step(&mut SimRng::new(0));
+
+        for &col in &[0, 2] {
+            assert!(matches!(fire_state.features[(0, col)], FireCell::Scorched { heat } if heat == fire_state.config.heat_per_fire));
+        }
+    }
+
+    #[test]
+    fn test_heat_saturates() {
+        let config = FireConfig { catch_chance: 0.0, max_heat: 255, heat_per_fire: 255, creep_heat: 1, creep_odds: 1, ..FireConfig::default() };
+        let mut fire_state = FireState::new(1, 3, config);
+        fire_state.features[(0, 0)] = FireCell::Lit { ttl: 10 };
+        fire_state.features[(0, 2)] = FireCell::Scorched { heat: 255 };
+
+        fire_state.step(&mut SimRng::new(0));
+
+        assert!(matches!(fire_state.features[(0, 1)], FireCell::Scorched { heat: 255 }));
+    }
+
+    #[test]
+    fn test_embers_cool_to_holes() {
+        let mut fire_state = FireState::new(1, 1, FireConfig::default());
+        fire_state.features[(0, 0)] = FireCell::Lit { ttl: 0 };
+
+        let mut rng = SimRng::new(0);
+        let mut temperatures = vec![];
+        loop {
+            fire_state.step(&mut rng);
+
+            match fire_state.features[(0, 0)] {
+                FireCell::Extinguished { temperature } => temperatures.push(temperature),
+                _ => panic!("extinguished cells should stay extinguished"),
+            }
+
+            if temperatures.last() == Some(&0) {
+                break;
+            }
+        }
+
+        let ember_temperature = FireConfig::default().ember_temperature;
+        assert_eq!(temperatures[0], ember_temperature);
+        assert!(temperatures.len() > ember_temperature as usize);
+    }
+
+    #[test]
+    fn test_flame_intensity_follows_ttl() {
+        let theme = Theme::default();
+        let config = FireConfig::default();
+        let glyph = |ttl| FireCell::Lit { ttl }.render(&theme, &config, 0.0).unwrap().glyph;
+
+        assert_eq!(glyph(super::PEAK_TTL + 5), '^');
+        assert_eq!(glyph(super::PEAK_TTL), '^');
+        assert_eq!(glyph(0), ',');
+    }
 }
@@ -0,0 +1,300 @@
+use std::mem;
+
+use grid::Grid;
+use rayon::prelude::*;
+
+use super::BAND_ROWS;
+use super::fire_state::{FireCell, FireState};
+use super::smoke_state::SmokeLayer;
+use serde::{Deserialize, Serialize};
+use theme::Theme;
+
+/// Tunables for the fluid smoke model.
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FluidSmokeConfig {
+    /// Upward velocity of the smoke, in cells per tick. Values outside `0.0..=1.0` are clamped.
+    pub buoyancy: f64,
+    /// Fraction of the difference from its neighbors' average that a cell gives up per tick.
+    pub diffusion: f64,
+    /// Fraction of density lost per tick.
+    pub decay: f64,
+    /// Density added per tick by each lit cell.
+    pub source: f64,
+}
+
+impl Default for FluidSmokeConfig {
+    fn default() -> Self {
+        FluidSmokeConfig {
+            buoyancy: 0.6,
+            diffusion: 0.15,
+            decay: 0.02,
+            source: 0.12,
+        }
+    }
+}
+
+impl FluidSmokeConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.diffusion) || !(0.0..=1.0).contains(&self.decay) {
+            return Err("diffusion and decay should be between 0 and 1".to_string());
+        }
+        if !(self.source.is_finite() && self.source >= 0.0) {
+            return Err("source should be at least 0".to_string());
+        }
+        if self.buoyancy.is_nan() {
+            return Err("buoyancy should be a number".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+/// Densities below this are rounded down to nothing so that smoke eventually clears completely.
+const MIN_DENSITY: f64 = 0.005;
+
+/// A grid-based smoke model: density is carried upward by buoyancy and sideways by wind, spreads out
+/// by diffusion, and slowly decays. Smoke pools beneath the top edge rather than escaping through it. Unless the inset
+/// is changed, the outermost ring of cells (where the `Border` is drawn) is treated as a wall too.
+#[derive(Clone)]
+pub struct FluidSmokeState {
+    rows: usize,
+    cols: usize,
+    /// How many cells in from the top and sides of the grid the walls begin.
+    inset: usize,
+    density: Grid<f64>,
+    /// Holds the smoke partway through a tick, between being carried along and spreading out.
+    scratch: Grid<f64>,
+    /// One row per band of rows, collecting the smoke that rose out of the top of the band while bands were being
+    /// stepped in parallel.
+    spills: Grid<f64>,
+    /// The density of the densest cell, as of the last tick and the one before.
+    densest: f64,
+    previously_densest: f64,
+    config: FluidSmokeConfig,
+}
+
+impl FluidSmokeState {
+    pub fn new(rows: usize, cols: usize, config: FluidSmokeConfig) -> Self {
+        Self {
+            rows,
+            cols,
+            inset: 1,
+            density: Grid::new(rows, cols, 0.0),
+            scratch: Grid::new(rows, cols, 0.0),
+            spills: Grid::new(rows.div_ceil(BAND_ROWS), cols, 0.0),
+            densest: 0.0,
+            previously_densest: 0.0,
+            config,
+        }
+    }
+
+    /// Walls smoke in `inset` cells from the top and sides of the grid, rather than the one-cell ring of a `Border`.
+    pub fn set_inset(&mut self, inset: usize) {
+        self.inset = inset;
+    }
+
+    fn is_wall(&self, row: usize, col: usize) -> bool {
+        row < self.inset || col < self.inset || col + self.inset >= self.cols
+    }
+
+    /// nearest_open moves (`row`, `col`) off of the wall, if the grid is big enough to allow it.
+    fn nearest_open(&self, row: usize, col: usize) -> (usize, usize) {
+        let inset = self.inset;
+        let row = if row < inset && self.rows > inset { inset } else { row };
+        let col = if col < inset && self.cols > 2 * inset {
+            inset
+        } else if col + inset >= self.cols && self.cols > 2 * inset {
+            self.cols - 1 - inset
+        } else {
+            col
+        };
+
+        (row, col)
+    }
+
+    /// destination is the cell that smoke moving from (`row`, `col`) by (`d_row`, `d_col`) ends up in. Any part of the
+    /// move that would go out of bounds or into the wall is cancelled, which is what makes smoke pool. Smoke never moves
+    /// more than one row up.
+    fn destination(&self, row: usize, col: usize, d_row: isize, d_col: isize) -> (usize, usize) {
+        let target_row = row as isize + d_row;
+        let target_col = col as isize + d_col;
+
+        let row_open = target_row >= 0 && (target_row as usize) < self.rows && !self.is_wall(target_row as usize, col);
+        let next_row = if row_open { target_row as usize } else { row };
+
+        let col_open = target_col >= 0 && (target_col as usize) < self.cols && !self.is_wall(next_row, target_col as usize);
+        let next_col = if col_open { target_col as usize } else { col };
+
+        (next_row, next_col)
+    }
+
+    /// advect moves density along the velocity field into `advected` using a mass-conserving upwind scheme. Bands of
+    /// rows are moved in parallel; what rises out of the top of a band is added to the band above once they're done.
+    fn advect(&self, wind: f64, advected: &mut Grid<f64>, spills: &mut Grid<f64>) {
+        let rise = self.config.buoyancy.clamp(0.0, 1.0);
+        let drift = wind.abs().min(1.0);
+        let d_col = if wind < 0.0 { -1 } else { 1 };
+        let cols = self.cols;
+
+        advected.par_bands_mut(BAND_ROWS).zip(spills.par_bands_mut(1)).enumerate().for_each(|(band, (cells, spill))| {
+            let first_row = band * BAND_ROWS;
+
+            for cell in cells.iter_mut().chain(spill.iter_mut()) {
+                *cell = 0.0;
+            }
+
+            for i in first_row..first_row + cells.len() / cols {
+                for j in 0..cols {
+                    let d = self.density[(i, j)];
+                    if d == 0.0 {
+                        continue;
+                    }
+
+                    let moves = [
+                        (0, 0, d * (1.0 - rise) * (1.0 - drift)),
+                        (-1, 0, d * rise * (1.0 - drift)),
+                        (0, d_col, d * (1.0 - rise) * drift),
+                        (-1, d_col, d * rise * drift),
+                    ];
+
+                    for &(d_row, d_col, amount) in &moves {
+                        match self.destination(i, j, d_row, d_col) {
+                            (row, col) if row >= first_row => cells[(row - first_row) * cols + col] += amount,
+                            (_, col) => spill[col] += amount,
+                        }
+                    }
+                }
+            }
+        });
+
+        for band in 1..spills.rows() {
+            for (j, &amount) in spills.row(band).iter().enumerate() {
+                advected[(band * BAND_ROWS - 1, j)] += amount;
+            }
+        }
+    }
+
+    /// diffuse relaxes each cell of `density` toward the average of its neighbors, then lets it decay, leaving the
+    /// result as the state's density. Walls and the grid's edges reflect, so diffusion loses no density.
+    fn diffuse(&mut self, density: &Grid<f64>) {
+        let mut diffused = mem::replace(&mut self.density, Grid::new(0, 0, 0.0));
+        let cols = self.cols;
+        let this = &*self;
+
+        diffused.par_bands_mut(BAND_ROWS).enumerate().for_each(|(band, cells)| {
+            for (ix, cell) in cells.iter_mut().enumerate() {
+                let (i, j) = (band * BAND_ROWS + ix / cols, ix % cols);
+                let d = density[(i, j)];
+
+                let mut next = if this.is_wall(i, j) {
+                    d
+                } else {
+                    let neighbor = |row: Option<usize>, col: Option<usize>| match (row, col) {
+                        (Some(row), Some(col)) if row < this.rows && col < this.cols && !this.is_wall(row, col) => density[(row, col)],
+                        _ => d,
+                    };
+
+                    let average = (neighbor(i.checked_sub(1), Some(j)) +
+                                   neighbor(Some(i + 1), Some(j)) +
+                                   neighbor(Some(i), j.checked_sub(1)) +
+                                   neighbor(Some(i), Some(j + 1))) / 4.0;
+
+                    d + this.config.diffusion * (average - d)
+                };
+
+                next *= 1.0 - this.config.decay;
+                *cell = if next < MIN_DENSITY { 0.0 } else { next };
+            }
+        });
+
+        self.density = diffused;
+    }
+
+    /// step advances the smoke by one tick, giving off more wherever `fire_state` is burning.
+    pub fn step(&mut self, fire_state: &FireState, wind: f64) {
+        // lit cells emit smoke into the nearest cell that isn't part of the wall
+        for i in 0..self.rows {
+            for j in 0..self.cols {
+                if let FireCell::Lit { .. } = fire_state.features[(i, j)] {
+                    let (row, col) = self.nearest_open(i, j);
+                    self.density[(row, col)] += self.config.source;
+                }
+            }
+        }
+
+        let mut scratch = mem::replace(&mut self.scratch, Grid::new(0, 0, 0.0));
+        let mut spills = mem::replace(&mut self.spills, Grid::new(0, 0, 0.0));
+
+        self.advect(wind, &mut scratch, &mut spills);
+        self.diffuse(&scratch);
+
+        self.scratch = scratch;
+        self.spills = spills;
+
+        self.previously_densest = self.densest;
+        self.densest = self.density.iter().cloned().fold(0.0, f64::max);
+    }
+
+    /// volume is the total density over the whole grid.
+    pub fn volume(&self) -> f64 {
+        self.density.iter().sum()
+    }
+
+    /// ticks_left is about how long the smoke will take to clear if no more is given off: long enough for its densest
+    /// cell to thin out below `MIN_DENSITY`. Smoke thins out at least as fast as it decays, and faster once it's
+    /// spreading out too, so whichever rate its densest cell fell at over the last tick is assumed to keep up. Smoke
+    /// that neither decays nor thins out never clears, which comes out as `u64::MAX`.
+    pub fn ticks_left(&self) -> u64 {
+        if self.densest < MIN_DENSITY {
+            return 0;
+        }
+
+        let decay_per_tick = -(1.0 - self.config.decay).ln();
+        let thinning_per_tick = (self.previously_densest / self.densest).ln();
+
+        // float-to-int casts saturate, so a rate of zero comes out as u64::MAX
+        ((self.densest / MIN_DENSITY).ln() / decay_per_tick.max(thinning_per_tick)).ceil() as u64
+    }
+
+    /// render draws the smoke into `layer`.
+    pub fn render(&self, theme: &Theme, layer: &mut SmokeLayer) {
+        layer.render(self.density.iter().cloned(), theme);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FluidSmokeConfig, FluidSmokeState};
+    use state::{FireConfig, FireState};
+
+    #[test]
+    fn test_smoke_pools_under_top_wall() {
+        let mut smoke = FluidSmokeState::new(6, 5, FluidSmokeConfig::default());
+        smoke.density[(4, 2)] = 1.0;
+
+        let fire = FireState::new(6, 5, FireConfig::default());
+        for _ in 0..20 {
+            smoke.step(&fire, 0.0);
+        }
+
+        assert!(smoke.density.row(0).iter().all(|&d| d == 0.0));
+        assert!(smoke.density[(1, 2)] > smoke.density[(4, 2)]);
+        assert!(smoke.volume() > 0.5);
+    }
+
+    #[test]
+    fn test_smoke_reaches_edges_without_walls() {
+        let mut smoke = FluidSmokeState::new(4, 3, FluidSmokeConfig::default());
+        smoke.set_inset(0);
+        smoke.density[(3, 0)] = 1.0;
+
+        let fire = FireState::new(4, 3, FireConfig::default());
+        for _ in 0..10 {
+            smoke.step(&fire, 0.0);
+        }
+
+        assert!(smoke.density[(0, 0)] > 0.0);
+        assert!(smoke.density[(0, 2)] > 0.0);
+    }
+}
@@ -1,56 +1,401 @@
+mod ash_state;
 mod fire_state;
+mod fluid_smoke_state;
+mod rng;
 mod smoke_state;
 
-use layers::{Compositor, IntermediateLayer};
+use std::rc::Rc;
 
-pub use self::fire_state::{FireState, FireLayer};
-use self::smoke_state::{SmokeState, SmokeLayer};
+use serde::{Deserialize, Serialize};
 
-pub struct CombustionState {
-    rows: usize,
-    cols: usize,
+use theme::Theme;
+
+pub use self::ash_state::{AshConfig, AshState, AshLayer};
+pub use self::fire_state::{FireConfig, FireState, FireLayer};
+pub use self::fluid_smoke_state::{FluidSmokeConfig, FluidSmokeState};
+pub use self::rng::SimRng;
+pub use self::smoke_state::{SmokeConfig, SmokeState, SmokeLayer};
+
+use self::fire_state::FireCell;
+
+/// Fire and smoke are stepped in bands of this many rows, in parallel. The bands are the same however many threads
+/// there are, so a seed always gives the same burn.
+const BAND_ROWS: usize = 8;
+
+/// Which simulation drives the smoke layer.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmokeModel {
+    /// Independent units of volume that hop upward and leave through the top edge.
+    Particles,
+    /// A density grid that rises, drifts with the wind, diffuses and pools under the top edge.
+    Fluid,
+}
+
+/// Everything that shapes how the burn plays out, as opposed to how it is drawn.
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SimulationConfig {
+    pub smoke_model: SmokeModel,
+    /// Horizontal wind speed in cells per tick, clamped to `-1.0..=1.0`; positive values blow to the right.
+    /// Blows falling and piled-up ash around, but of the smoke models only the fluid one is affected.
+    pub wind: f64,
+    /// The most ticks the simulation runs for, should it somehow never die down. This counts ticks rather than time, so
+    /// it cuts a burn short at the same point however long ticks last, and in `burn sim` and exports too;
+    /// `rendering.max_burn_ms` limits how long a burn plays for on the clock.
+    pub max_ticks: u64,
+    pub fire: FireConfig,
+    /// Used by the `particles` smoke model.
+    pub particles: SmokeConfig,
+    /// Used by the `fluid` smoke model.
+    pub fluid: FluidSmokeConfig,
+    pub ash: AshConfig,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        SimulationConfig {
+            smoke_model: SmokeModel::Fluid,
+            wind: 0.0,
+            max_ticks: 6000,
+            fire: FireConfig::default(),
+            particles: SmokeConfig::default(),
+            fluid: FluidSmokeConfig::default(),
+            ash: AshConfig::default(),
+        }
+    }
+}
+
+impl SimulationConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_ticks == 0 {
+            return Err("[simulation] `max_ticks` should be at least 1".to_string());
+        }
+        if !self.wind.is_finite() {
+            return Err("[simulation] `wind` should be a number of cells per tick".to_string());
+        }
+        self.fire.validate().map_err(|err| format!("[simulation.fire] {}", err))?;
+        self.particles.validate().map_err(|err| format!("[simulation.particles] {}", err))?;
+        self.fluid.validate().map_err(|err| format!("[simulation.fluid] {}", err))?;
+        self.ash.validate().map_err(|err| format!("[simulation.ash] {}", err))
+    }
+}
+
+#[derive(Clone)]
+enum Smoke {
+    Particles(SmokeState),
+    Fluid(FluidSmokeState),
+}
+
+impl Smoke {
+    fn new(rows: usize, cols: usize, config: &SimulationConfig) -> Self {
+        match config.smoke_model {
+            SmokeModel::Particles => Smoke::Particles(SmokeState::new(rows, cols, config.particles)),
+            SmokeModel::Fluid => Smoke::Fluid(FluidSmokeState::new(rows, cols, config.fluid)),
+        }
+    }
+
+    fn step(&mut self, fire_state: &FireState, wind: f64, rng: &mut SimRng) {
+        match *self {
+            Smoke::Particles(ref mut smoke_state) => smoke_state.step(fire_state, rng),
+            Smoke::Fluid(ref mut smoke_state) => smoke_state.step(fire_state, wind),
+        }
+    }
+
+    fn set_inset(&mut self, inset: usize) {
+        // particles leave through the top and have no walls to move
+        if let Smoke::Fluid(ref mut smoke_state) = *self {
+            smoke_state.set_inset(inset);
+        }
+    }
+
+    fn volume(&self) -> f64 {
+        match *self {
+            Smoke::Particles(ref smoke_state) => smoke_state.volume(),
+            Smoke::Fluid(ref smoke_state) => smoke_state.volume(),
+        }
+    }
+
+    fn ticks_left(&self) -> u64 {
+        match *self {
+            Smoke::Particles(ref smoke_state) => smoke_state.ticks_left(),
+            Smoke::Fluid(ref smoke_state) => smoke_state.ticks_left(),
+        }
+    }
+
+    fn render(&self, theme: &Theme, layer: &mut SmokeLayer) {
+        match *self {
+            Smoke::Particles(ref smoke_state) => smoke_state.render(theme, layer),
+            Smoke::Fluid(ref smoke_state) => smoke_state.render(theme, layer),
+        }
+    }
+}
+
+/// A summary of one tick of the simulation.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Stats {
+    /// Cells on fire.
+    pub lit: usize,
+    /// Cells that have burned out, whether still glowing or cold.
+    pub extinguished: usize,
+    /// Total smoke: units of volume for the particle model, or summed density for the fluid one.
+    pub smoke: f64,
+    /// The highest row with a flame in it, counting down from the top of the page. Fire starts on the bottom row and
+    /// climbs, so this tracks how far up the page it has reached; `None` once nothing is burning.
+    pub front: Option<usize>,
+}
 
+/// The whole simulation: fire, smoke and ash, plus the layers they were last drawn into. Stepping and drawing reuse the
+/// same buffers every tick, so neither allocates, and the layers are only redrawn when they're asked for.
+#[derive(Clone)]
+pub struct CombustionState {
     fire_state: FireState,
-    smoke_state: SmokeState,
+    smoke: Smoke,
+    ash_state: AshState,
+
+    fire_layer: FireLayer,
+    smoke_layer: SmokeLayer,
+    ash_layer: AshLayer,
+    /// Whether the layers are behind the simulation.
+    stale: bool,
+
+    theme: Rc<Theme>,
+    flicker: bool,
+    wind: f64,
+    rng: SimRng,
+    ticks: u64,
+    max_ticks: u64,
 }
 
 impl CombustionState {
-    pub fn new(rows: usize, cols: usize) -> Self {
-        let fire_state = FireState::new(rows, cols);
-        let smoke_state = SmokeState::new(rows, cols);
+    /// new sets up an unlit page. Every random choice from here on is drawn from a generator seeded with `seed`.
+    pub fn new(rows: usize, cols: usize, config: &SimulationConfig, seed: u64) -> Self {
+        let fire_state = FireState::new(rows, cols, config.fire);
+        let smoke = Smoke::new(rows, cols, config);
+        let ash_state = AshState::new(rows, cols, config.ash);
 
         CombustionState {
-            rows,
-            cols,
             fire_state,
-            smoke_state,
+            smoke,
+            ash_state,
+            fire_layer: FireLayer::new(rows, cols),
+            smoke_layer: SmokeLayer::new(rows, cols, false),
+            ash_layer: AshLayer::new(rows, cols),
+            stale: true,
+            theme: Rc::new(Theme::default()),
+            flicker: false,
+            wind: config.wind.clamp(-1.0, 1.0),
+            rng: SimRng::new(seed),
+            ticks: 0,
+            max_ticks: config.max_ticks,
         }
     }
 
+    /// Sets the glyphs and colors that fire, embers, ash and smoke are drawn with.
+    pub fn set_theme(&mut self, theme: Rc<Theme>) {
+        self.theme = theme;
+        self.stale = true;
+    }
+
+    /// When enabled, sparse smoke dims the layers beneath it instead of hiding them.
+    pub fn set_translucent_smoke(&mut self, translucent_smoke: bool) {
+        self.smoke_layer.set_translucent(translucent_smoke);
+        self.stale = true;
+    }
+
+    /// When enabled, flames flicker in patches of neighboring cells rather than all burning steadily.
+    pub fn set_flicker(&mut self, flicker: bool) {
+        self.flicker = flicker;
+        self.stale = true;
+    }
+
+    /// Sets how many cells in from the edges of the page smoke and ash are walled in. The default of 1 keeps them off
+    /// of a `Border` drawn around the page; with nothing drawn there, 0 lets them reach the edges.
+    pub fn set_inset(&mut self, inset: usize) {
+        self.smoke.set_inset(inset);
+        self.ash_state.set_inset(inset);
+    }
+
     pub fn start_fire(&mut self) {
-        self.fire_state.start_fire();
+        self.fire_state.start_fire(&mut self.rng);
+        self.stale = true;
     }
 
-    pub fn get_next(&self) -> Self {
-        let next_fire_state = self.fire_state.get_next();
-        let next_smoke_state = self.smoke_state.get_next(&next_fire_state);
+    /// step advances the simulation by one tick.
+    pub fn step(&mut self) {
+        self.fire_state.step(&mut self.rng);
+        self.smoke.step(&self.fire_state, self.wind, &mut self.rng);
+        self.ash_state.step(&self.fire_state, self.wind, &mut self.rng);
 
-        CombustionState {
-            rows: self.rows,
-            cols: self.cols,
-            fire_state: next_fire_state,
-            smoke_state: next_smoke_state,
+        self.ticks += 1;
+        self.stale = true;
+    }
+
+    /// render brings the layers up to date with the simulation, if they aren't already.
+    pub fn render(&mut self) {
+        if !self.stale {
+            return;
         }
+
+        self.fire_state.render(&self.theme, self.flicker, &mut self.fire_layer);
+        self.smoke.render(&self.theme, &mut self.smoke_layer);
+        self.ash_state.render(&self.theme, &mut self.ash_layer);
+        self.stale = false;
+    }
+
+    pub fn stats(&self) -> Stats {
+        let fire = &self.fire_state;
+
+        Stats {
+            lit: fire.count(|cell| matches!(cell, FireCell::Lit { .. })),
+            extinguished: fire.count(|cell| matches!(cell, FireCell::Extinguished { .. })),
+            smoke: self.smoke.volume(),
+            front: fire.features.iter_rows().position(|row| row.iter().any(|&cell| matches!(cell, FireCell::Lit { .. }))),
+        }
+    }
+
+    /// ticks is how many times the simulation has been stepped.
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    /// is_over is whether the fire has gone out and the last of its smoke has cleared, or the burn has run for
+    /// `max_ticks` without getting there. Embers may still be cooling and ash settling.
+    pub fn is_over(&self) -> bool {
+        (!self.fire_state.is_burning() && self.smoke.volume() == 0.0) || self.ticks >= self.max_ticks
+    }
+
+    /// ticks_left guesses how many more ticks the burn has to go before it's over, from how quickly the fire has spread
+    /// so far and how long the smoke it has given off will take to clear. The guess is rough while the fire is young,
+    /// and gets better as the burn goes on.
+    pub fn ticks_left(&self) -> u64 {
+        let left = self.fire_state.ticks_left(self.ticks).saturating_add(self.smoke.ticks_left());
+        left.min(self.max_ticks.saturating_sub(self.ticks))
+    }
+
+    // fire, ash and smoke are handed out as separate layers so that smoke can blend with everything underneath it,
+    // not just the fire. They're only as fresh as the last call to `render`
+    pub fn fire_layer(&self) -> &FireLayer {
+        &self.fire_layer
+    }
+
+    pub fn ash_layer(&self) -> &AshLayer {
+        &self.ash_layer
     }
 
-    pub fn is_saturated(&self) -> bool {
-        self.fire_state.is_saturated()
+    pub fn smoke_layer(&self) -> &SmokeLayer {
+        &self.smoke_layer
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CombustionState, FireCell, SimulationConfig, SmokeModel, Stats};
+    use state::FireConfig;
+    use proptest::prelude::*;
+    use rayon::ThreadPoolBuilder;
+
+    fn run(config: &SimulationConfig, seed: u64, threads: usize) -> Vec<Stats> {
+        let pool = ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
 
-    // TODO: improve when `impl Trait` lands?
-    pub fn as_layer(&self) -> IntermediateLayer {
-        let compositor = Compositor { rows: self.rows, cols: self.cols };
+        pool.install(|| {
+            // tall enough for several bands of rows, so that smoke crosses from one band into the next
+            let mut state = CombustionState::new(40, 20, config, seed);
+            state.start_fire();
 
-        compositor.intermediate_composite(&[&self.fire_state.as_layer(), &self.smoke_state.as_layer()])
+            (0..60).map(|_| {
+                state.step();
+                state.stats()
+            }).collect()
+        })
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_same_seed_same_burn() {
+        for &smoke_model in &[SmokeModel::Particles, SmokeModel::Fluid] {
+            let config = SimulationConfig { smoke_model, wind: 0.3, ..SimulationConfig::default() };
+
+            assert_eq!(run(&config, 7, 1), run(&config, 7, 1));
+            assert_eq!(run(&config, 7, 1), run(&config, 7, 4));
+            assert_ne!(run(&config, 7, 1), run(&config, 8, 1));
+        }
+    }
+
+    #[test]
+    fn test_over_once_fire_and_smoke_are_gone() {
+        // a fire that can't spread goes out well before reaching the top of the page
+        let fire = FireConfig { catch_chance: 0.0, ..FireConfig::default() };
+
+        for &smoke_model in &[SmokeModel::Particles, SmokeModel::Fluid] {
+            let config = SimulationConfig { smoke_model, fire, ..SimulationConfig::default() };
+            let mut state = CombustionState::new(20, 20, &config, 1);
+            state.start_fire();
+
+            while !state.is_over() {
+                assert!(state.ticks() < 1000, "the burn should end once the fire dies out");
+                state.step();
+            }
+
+            let stats = state.stats();
+            assert_eq!((stats.lit, stats.extinguished, stats.smoke), (0, 1, 0.0));
+        }
+
+        let config = SimulationConfig { max_ticks: 5, ..SimulationConfig::default() };
+        let mut state = CombustionState::new(20, 20, &config, 1);
+        state.start_fire();
+        for _ in 0..5 {
+            assert!(!state.is_over());
+            state.step();
+        }
+        assert!(state.is_over());
+    }
+
+    fn smoke_model() -> impl Strategy<Value = SmokeModel> {
+        prop_oneof![Just(SmokeModel::Particles), Just(SmokeModel::Fluid)]
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(48))]
+
+        #[test]
+        fn test_automaton_invariants(
+            rows in 1usize..24,
+            cols in 1usize..24,
+            wind in -1.0f64..1.0,
+            smoke_model in smoke_model(),
+            seed in any::<u64>(),
+        ) {
+            let config = SimulationConfig { smoke_model, wind, ..SimulationConfig::default() };
+            // the most smoke a single lit cell can give off in a tick
+            let emission = match smoke_model {
+                SmokeModel::Particles => 1.0,
+                SmokeModel::Fluid => config.fluid.source,
+            };
+
+            let mut state = CombustionState::new(rows, cols, &config, seed);
+            state.start_fire();
+
+            while !state.is_over() && state.ticks() < 300 {
+                let before = state.fire_state.features.clone();
+                let smoke = state.smoke.volume();
+                state.step();
+
+                for (&was, &is) in before.iter().zip(state.fire_state.features.iter()) {
+                    match (was, is) {
+                        (FireCell::Extinguished { .. }, FireCell::Extinguished { .. }) => {},
+                        (FireCell::Extinguished { .. }, _) => prop_assert!(false, "an extinguished cell relit"),
+                        (FireCell::Lit { ttl: 0 }, FireCell::Extinguished { .. }) => {},
+                        (FireCell::Lit { ttl: was }, FireCell::Lit { ttl: is }) => prop_assert_eq!(is + 1, was),
+                        (FireCell::Lit { .. }, _) => prop_assert!(false, "a flame didn't burn down"),
+                        _ => {},
+                    }
+                }
+
+                let volume = state.smoke.volume();
+                let lit = state.stats().lit as f64;
+                prop_assert!(volume.is_finite() && volume >= 0.0, "smoke volume {}", volume);
+                prop_assert!(volume <= smoke + lit * emission + 1e-9, "smoke grew from {} to {} with {} lit", smoke, volume, lit);
+            }
+        }
+    }
+}
@@ -0,0 +1,83 @@
+/// Added to a seed to get to the next one, as in splitmix64.
+const GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// Drives every random choice the simulation makes, so that a burn started from the same seed, at the same size and
+/// with the same settings plays out the same way every time. This is wyrand: one multiply per number, which matters
+/// when a big page makes a few random choices per cell per tick.
+#[derive(Clone, Debug)]
+pub struct SimRng {
+    state: u64,
+}
+
+impl SimRng {
+    /// new spreads `seed` out with splitmix64 first, so that nearby seeds give unrelated burns.
+    pub fn new(seed: u64) -> Self {
+        let mut z = seed.wrapping_add(GAMMA);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+
+        SimRng { state: z ^ (z >> 31) }
+    }
+
+    /// stream gives the `n`th of a family of independent generators that share `seed`. Parts of the grid that are
+    /// stepped in parallel each draw from their own stream, so the result doesn't depend on which thread runs what.
+    pub fn stream(seed: u64, n: u64) -> Self {
+        Self::new(seed.wrapping_add(n.wrapping_mul(GAMMA)))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0xa076_1d64_78bd_642f);
+        let t = u128::from(self.state) * u128::from(self.state ^ 0xe703_7ed1_a0b4_28db);
+
+        (t >> 64) as u64 ^ t as u64
+    }
+
+    /// next_f64 is uniform over `0.0..1.0`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// below picks a number in `0..n`, which must not be empty.
+    pub fn below(&mut self, n: usize) -> usize {
+        debug_assert!(n > 0);
+        ((u128::from(self.next_u64()) * n as u128) >> 64) as usize
+    }
+
+    /// between picks a number in `low..high`, which must not be empty.
+    pub fn between(&mut self, low: isize, high: isize) -> isize {
+        low + self.below((high - low) as usize) as isize
+    }
+
+    /// one_in is true with a chance of 1 in `n`, and always true for an `n` of 0 or 1.
+    pub fn one_in(&mut self, n: u32) -> bool {
+        n <= 1 || self.below(n as usize) == 0
+    }
+
+    pub fn coin(&mut self) -> bool {
+        self.next_u64() >> 63 == 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SimRng;
+
+    #[test]
+    fn test_ranges() {
+        let mut rng = SimRng::new(0);
+        let mut seen = [false; 4];
+
+        for _ in 0..1000 {
+            let n = rng.between(-1, 3);
+            assert!((-1..3).contains(&n));
+            seen[(n + 1) as usize] = true;
+
+            let f = rng.next_f64();
+            assert!((0.0..1.0).contains(&f));
+        }
+
+        assert!(seen.iter().all(|&seen| seen));
+        assert!(rng.one_in(1));
+        assert_ne!(SimRng::new(1).next_u64(), SimRng::new(2).next_u64());
+    }
+}
@@ -1,22 +1,57 @@
-use layers::Layerable;
+use std::mem;
+
+use grid::Grid;
+use layers::{Cell, Layerable};
+use palette;
+use rayon::prelude::*;
 use termion::color;
-use rand::{self, Rng};
+use serde::{Deserialize, Serialize};
+use theme::Theme;
 
+use super::BAND_ROWS;
+use super::rng::SimRng;
 use super::fire_state::FireState;
 
-const SMOKE_GLYPHS: &[char] = &[
-    '"',
-    '&',
-    '@',
-    '?',
-];
+/// Volume at which a cell is drawn with the densest glyph and shade.
+const DENSE_VOLUME: usize = 6;
+
+/// Translucent smoke only dims the cell below it while its density is under this threshold.
+const TRANSLUCENT_DENSITY: f64 = 0.5;
+
+/// Stand-in for the terminal's default foreground color when dimming uncolored text.
+const DEFAULT_FG: color::Rgb = color::Rgb(200, 200, 200);
+
+/// Tunables for the particle smoke model.
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SmokeConfig {
+    /// Each tick, a burning cell has a 1-in-`spawn_odds` chance of giving off a unit of smoke.
+    pub spawn_odds: u32,
+    /// Each tick, a unit of smoke rises between 1 and `max_rise` rows.
+    pub max_rise: usize,
+    /// Each unit of volume has a 1-in-`dissipation_odds` chance of dissipating per tick.
+    pub dissipation_odds: u32,
+}
+
+impl Default for SmokeConfig {
+    fn default() -> Self {
+        SmokeConfig {
+            spawn_odds: 10,
+            max_rise: 8,
+            dissipation_odds: 12,
+        }
+    }
+}
+
+impl SmokeConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_rise == 0 {
+            return Err("max_rise should be at least 1".to_string());
+        }
 
-const SMOKE_COLORS: &[color::Rgb] = &[
-    color::Rgb(79, 79, 79),
-    color::Rgb(140, 133, 133),
-    color::Rgb(178, 173, 173),
-    color::Rgb(140, 120, 120),
-];
+        Ok(())
+    }
+}
 
 // TODO: maybe just alias Option<usize> to reap method impls?
 #[derive(Copy, Clone)]
@@ -27,47 +62,55 @@ enum SmokeCell {
     },
 }
 
-impl From<SmokeCell> for Option<String> {
-    fn from(smoke_cell: SmokeCell) -> Self {
-        use self::SmokeCell::{Clear, Smoky};
-
-        let mut rng = rand::thread_rng();
-
-        match smoke_cell {
-            Clear => None,
-            Smoky { .. } => {
-                let glyph = *(rng.choose(SMOKE_GLYPHS).unwrap());
-                let smoke_color = *(rng.choose(SMOKE_COLORS).unwrap());
-
-                Some(format!("{}{}{}", color::Fg(smoke_color), glyph, color::Fg(color::Reset)))
-            }
+impl SmokeCell {
+    /// density maps volume onto `0.0..=1.0`, where `1.0` is anything at or above `DENSE_VOLUME`.
+    fn density(self) -> f64 {
+        match self {
+            SmokeCell::Clear => 0.0,
+            SmokeCell::Smoky { volume } => volume.min(DENSE_VOLUME) as f64 / DENSE_VOLUME as f64,
         }
     }
 }
 
+/// render_density draws smoke of the given density, or nothing for a density of zero.
+fn render_density(theme: &Theme, density: f64) -> Option<Cell> {
+    if density <= 0.0 {
+        return None;
+    }
+
+    Some(theme.smoke.cell_at(density))
+}
+
+/// The particle smoke model: each unit of volume hops upward independently every tick and is lost once it
+/// leaves the top of the grid.
 #[derive(Clone)]
 pub struct SmokeState {
-    rows: usize,
     cols: usize,
-    features: Vec<Vec<SmokeCell>>,
+    features: Grid<SmokeCell>,
+    /// Where the next tick is written before it's swapped into `features`.
+    next: Grid<SmokeCell>,
+    /// For each band of rows, the cells that smoke rose into above the band, as (row, col); kept between ticks so
+    /// that they don't have to be allocated again.
+    spills: Vec<Vec<(usize, usize)>>,
+    config: SmokeConfig,
 }
 
 impl SmokeState {
-    pub fn new(rows: usize, cols: usize) -> Self {
-        let features = vec![vec![SmokeCell::Clear; cols]; rows];
+    pub fn new(rows: usize, cols: usize, config: SmokeConfig) -> Self {
+        let features = Grid::new(rows, cols, SmokeCell::Clear);
 
         Self {
-            rows,
             cols,
+            next: features.clone(),
             features,
+            spills: vec![vec![]; rows.div_ceil(BAND_ROWS)],
+            config,
         }
     }
 
-    fn gen_smoke_movement(&self, row: usize, col: usize) -> Option<(usize, usize)> {
-        let mut rng = rand::thread_rng();
-
-        let row_delta = rng.gen_range::<isize>(-8, 0);
-        let col_delta = rng.gen_range::<isize>(-1, 2);
+    fn gen_smoke_movement(&self, row: usize, col: usize, rng: &mut SimRng) -> Option<(usize, usize)> {
+        let row_delta = -rng.between(1, self.config.max_rise as isize + 1);
+        let col_delta = rng.between(-1, 2);
 
         let new_row = row as isize + row_delta;
         let new_col = col as isize + col_delta;
@@ -81,84 +124,149 @@ impl SmokeState {
         Some((new_row as usize, new_col as usize))
     }
 
-    fn place_smoke(&mut self, row: usize, col: usize) {
-        let features = &mut self.features;
-        let next = match features[row][col] {
+    fn place_smoke(cell: &mut SmokeCell) {
+        *cell = match *cell {
             SmokeCell::Clear => SmokeCell::Smoky { volume: 1 },
             SmokeCell::Smoky { volume: vol } => SmokeCell::Smoky { volume: vol + 1},
         };
-
-        features[row][col] = next;
-    }
-
-    fn clear_smoke(&mut self, row: usize, col: usize) {
-        self.features[row][col] = SmokeCell::Clear;
     }
 
-    pub fn get_next(&self, fire_state: &FireState) -> Self {
+    /// step advances the smoke by one tick, giving off more wherever `fire_state` is burning. Bands of rows are stepped
+    /// in parallel, each drawing from its own stream of random numbers seeded from `rng`.
+    pub fn step(&mut self, fire_state: &FireState, rng: &mut SimRng) {
         use super::fire_state::FireCell;
 
-        let mut rng = rand::thread_rng();
+        let seed = rng.next_u64();
+        let cols = self.cols;
+        let mut next = mem::replace(&mut self.next, Grid::new(0, 0, SmokeCell::Clear));
+        let mut spills = mem::take(&mut self.spills);
+
+        next.par_bands_mut(BAND_ROWS).zip(spills.par_iter_mut()).enumerate().for_each(|(band, (cells, spill))| {
+            let mut rng = SimRng::stream(seed, band as u64);
+            let first_row = band * BAND_ROWS;
 
-        let mut next = self.clone();
+            // every unit of smoke moves up at least one row, so nothing is left where it was
+            for cell in cells.iter_mut() {
+                *cell = SmokeCell::Clear;
+            }
 
-        // update positions of extant smokebits
-        for (i, row) in (&self.features).into_iter().enumerate() {
-            for (j, cell) in row.into_iter().enumerate() {
-                if let SmokeCell::Smoky { volume: vol } = *cell {
-                    for _ in 0..vol {
-                        if let Some((new_row, new_col)) = self.gen_smoke_movement(i, j) {
-                            next.place_smoke(new_row, new_col);
+            for i in first_row..first_row + cells.len() / cols {
+                for j in 0..cols {
+                    // update positions of extant smokebits, letting some of them dissipate along the way
+                    if let SmokeCell::Smoky { volume: vol } = self.features[(i, j)] {
+                        for _ in 0..vol {
+                            if rng.one_in(self.config.dissipation_odds) {
+                                continue;
+                            }
+
+                            match self.gen_smoke_movement(i, j, &mut rng) {
+                                Some((new_row, new_col)) if new_row >= first_row => {
+                                    Self::place_smoke(&mut cells[(new_row - first_row) * cols + new_col]);
+                                },
+                                Some(target) => spill.push(target),
+                                None => {},
+                            }
+                        }
+                    }
+
+                    // spawn new smokebits based on underlying fire layer
+                    if let FireCell::Lit { .. } = fire_state.features[(i, j)] {
+                        if rng.one_in(self.config.spawn_odds) {
+                            Self::place_smoke(&mut cells[(i - first_row) * cols + j]);
                         }
                     }
-                    next.clear_smoke(i, j);
                 }
             }
-        }
+        });
 
-        // spawn new smokebits based on underlying fire layer
-        for (i, row) in (&fire_state.features).into_iter().enumerate() {
-            for (j, cell) in row.into_iter().enumerate() {
-                match (*cell, rng.gen_weighted_bool(10)) {
-                    (FireCell::Lit { .. }, true) => next.place_smoke(i, j),
-                    _ => {},
-                }
+        for spill in spills.iter_mut() {
+            for (row, col) in spill.drain(..) {
+                Self::place_smoke(&mut next[(row, col)]);
             }
         }
 
-        next
+        self.next = next;
+        self.spills = spills;
+        mem::swap(&mut self.features, &mut self.next);
     }
 
-    pub fn as_layer(&self) -> SmokeLayer {
-        self.into()
+    /// volume is the total amount of smoke, in units of volume.
+    pub fn volume(&self) -> f64 {
+        self.features.iter()
+            .map(|&cell| if let SmokeCell::Smoky { volume } = cell { volume as f64 } else { 0.0 })
+            .sum()
+    }
+
+    /// ticks_left is about how long the smoke will take to clear if no more is given off: long enough for the lowest
+    /// of it to rise off the top of the page at an average speed.
+    pub fn ticks_left(&self) -> u64 {
+        let lowest = self.features.iter_rows()
+            .enumerate()
+            .filter(|(_, row)| row.iter().any(|&cell| matches!(cell, SmokeCell::Smoky { .. })))
+            .map(|(ix, _)| ix)
+            .last();
+        let average_rise = self.config.max_rise.div_ceil(2);
+
+        lowest.map_or(0, |row| (row / average_rise) as u64 + 1)
+    }
+
+    /// render draws the smoke into `layer`.
+    pub fn render(&self, theme: &Theme, layer: &mut SmokeLayer) {
+        layer.render(self.features.iter().map(|&cell| cell.density()), theme);
     }
 }
 
+/// Smoke as drawn, from either smoke model. Each cell's density is kept too, since thin enough smoke can be
+/// translucent, veiling the cell beneath it instead of hiding it.
+#[derive(Clone)]
 pub struct SmokeLayer {
-    rows: usize,
-    cols: usize,
-    features: Vec<Vec<Option<String>>>,
+    features: Grid<Option<Cell>>,
+    densities: Grid<f64>,
+    translucent: bool,
 }
 
 impl Layerable for SmokeLayer {
-    fn rows(&self) -> usize { self.rows }
-    fn cols (&self) -> usize { self.cols }
-    fn features(&self) -> &Vec<Vec<Option<String>>> { &self.features }
-}
+    fn rows(&self) -> usize { self.features.rows() }
+    fn cols (&self) -> usize { self.features.cols() }
+    fn features(&self) -> &Grid<Option<Cell>> { &self.features }
+
+    fn blend(&self, row_ix: usize, col_ix: usize, below: Option<Cell>) -> Option<Cell> {
+        let density = self.densities[(row_ix, col_ix)];
 
-impl <'a> From<&'a SmokeState> for SmokeLayer {
-    fn from(smoke_state: &'a SmokeState) -> Self {
-        let features: Vec<Vec<Option<String>>> =
-            (&smoke_state.features).into_iter()
-            .map(|row| {
-                row.into_iter().map(|&cell| cell.into()).collect::<Vec<Option<String>>>()
-            })
-            .collect::<Vec<_>>();
+        match (self.get(row_ix, col_ix), below) {
+            (Some(smoke), Some(under)) if self.translucent && density < TRANSLUCENT_DENSITY => {
+                let smoke_color = smoke.fg.unwrap_or(DEFAULT_FG);
+                let under_color = under.fg.unwrap_or(DEFAULT_FG);
+                // scale so that the thickest translucent smoke has (almost) replaced the color beneath it
+                let veil = density / TRANSLUCENT_DENSITY;
 
+                Some(Cell::colored(under.glyph, palette::mix(under_color, smoke_color, veil)))
+            },
+            (smoke, under) => smoke.or(under),
+        }
+    }
+}
+
+impl SmokeLayer {
+    /// new sets up an empty layer. When `translucent` is set, sparse smoke dims the cells beneath it rather than
+    /// covering them.
+    pub fn new(rows: usize, cols: usize, translucent: bool) -> Self {
         SmokeLayer {
-            features,
-            rows: smoke_state.rows,
-            cols: smoke_state.cols,
+            features: Grid::new(rows, cols, None),
+            densities: Grid::new(rows, cols, 0.0),
+            translucent,
         }
     }
-}
\ No newline at end of file
+
+    pub fn set_translucent(&mut self, translucent: bool) {
+        self.translucent = translucent;
+    }
+
+    /// render draws a grid's worth of smoke densities, row by row, each in `0.0..=1.0` (larger values are clamped).
+    pub fn render<I: Iterator<Item = f64>>(&mut self, densities: I, theme: &Theme) {
+        for ((density, stored), feature) in densities.zip(self.densities.iter_mut()).zip(self.features.iter_mut()) {
+            *stored = density;
+            *feature = render_density(theme, density);
+        }
+    }
+}
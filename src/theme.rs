@@ -0,0 +1,265 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use termion::color;
+use toml;
+
+use config;
+use layers::Cell;
+use palette;
+
+/// Themes that ship with `burn`, as (name, TOML source) pairs.
+const BUILTIN_THEMES: &[(&str, &str)] = &[
+    ("classic", include_str!("../themes/classic.toml")),
+    ("chemical", include_str!("../themes/chemical.toml")),
+    ("witchfire", include_str!("../themes/witchfire.toml")),
+    ("monochrome", include_str!("../themes/monochrome.toml")),
+];
+
+pub const DEFAULT_THEME: &str = "classic";
+
+/// A sequence of glyphs and colors that a simulation state steps through, from weakest to strongest.
+#[derive(Clone, Debug)]
+pub struct Ramp {
+    pub glyphs: Vec<char>,
+    /// May be empty, in which case cells use the terminal's default color.
+    pub colors: Vec<color::Rgb>,
+}
+
+impl Ramp {
+    /// cell_at draws the ramp at `t`, where `0.0` is the weakest stop and `1.0` the strongest.
+    /// Glyphs are stepped through; colors are blended.
+    pub fn cell_at(&self, t: f64) -> Cell {
+        Cell {
+            glyph: palette::step(&self.glyphs, t),
+            fg: self.color_at(t),
+        }
+    }
+
+    pub fn color_at(&self, t: f64) -> Option<color::Rgb> {
+        if self.colors.is_empty() {
+            None
+        } else {
+            Some(palette::gradient(&self.colors, t))
+        }
+    }
+
+    /// pick draws an arbitrary glyph and color from the ramp, chosen by `glyph_ix` and `color_ix`.
+    pub fn pick(&self, glyph_ix: usize, color_ix: usize) -> Cell {
+        Cell {
+            glyph: self.glyphs[glyph_ix % self.glyphs.len()],
+            fg: if self.colors.is_empty() { None } else { Some(self.colors[color_ix % self.colors.len()]) },
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BorderStyle {
+    pub horizontal: char,
+    pub vertical: char,
+    pub top_left: char,
+    pub top_right: char,
+    pub bottom_left: char,
+    pub bottom_right: char,
+    pub color: Option<color::Rgb>,
+}
+
+impl BorderStyle {
+    pub fn cell(&self, glyph: char) -> Cell {
+        Cell { glyph, fg: self.color }
+    }
+}
+
+/// The glyphs and colors used to draw each stage of the burn.
+#[derive(Clone, Debug)]
+pub struct Theme {
+    pub fire: Ramp,
+    /// Only the colors are used; scorching recolors the text underneath.
+    pub scorch: Ramp,
+    pub embers: Ramp,
+    /// Ash particles pick from these at random rather than by position.
+    pub ash: Ramp,
+    pub smoke: Ramp,
+    pub border: BorderStyle,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThemeFile {
+    fire: RampFile,
+    scorch: RampFile,
+    embers: RampFile,
+    ash: RampFile,
+    smoke: RampFile,
+    border: BorderFile,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RampFile {
+    #[serde(default)]
+    glyphs: Vec<char>,
+    #[serde(default)]
+    colors: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BorderFile {
+    glyphs: String,
+    color: Option<String>,
+}
+
+/// parse_color reads a color written as `#rrggbb`.
+fn parse_color(hex: &str) -> Result<color::Rgb, String> {
+    let digits = hex.trim_start_matches('#');
+    let channel = |ix: usize| digits.get(ix..ix + 2).and_then(|pair| u8::from_str_radix(pair, 16).ok());
+
+    match (digits.len(), channel(0), channel(2), channel(4)) {
+        (6, Some(r), Some(g), Some(b)) if hex.starts_with('#') => Ok(color::Rgb(r, g, b)),
+        _ => Err(format!("`{}` is not a color; colors are written as \"#rrggbb\"", hex)),
+    }
+}
+
+impl RampFile {
+    fn into_ramp(self, section: &str, needs_glyphs: bool) -> Result<Ramp, String> {
+        if needs_glyphs && self.glyphs.is_empty() {
+            return Err(format!("[{}] needs at least one glyph", section));
+        }
+        if !needs_glyphs && self.colors.is_empty() {
+            return Err(format!("[{}] needs at least one color", section));
+        }
+
+        let colors = self.colors.iter().map(|hex| parse_color(hex)).collect::<Result<Vec<_>, _>>()
+            .map_err(|err| format!("[{}] {}", section, err))?;
+
+        Ok(Ramp { glyphs: self.glyphs, colors })
+    }
+}
+
+impl Theme {
+    /// parse reads a theme from TOML.
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let file: ThemeFile = toml::from_str(source).map_err(|err| err.to_string())?;
+
+        let border_glyphs: Vec<char> = file.border.glyphs.chars().collect();
+        if border_glyphs.len() != 6 {
+            return Err("[border] glyphs should be exactly 6 characters: horizontal, vertical, top-left, top-right, \
+                        bottom-left and bottom-right".to_string());
+        }
+
+        let border = BorderStyle {
+            horizontal: border_glyphs[0],
+            vertical: border_glyphs[1],
+            top_left: border_glyphs[2],
+            top_right: border_glyphs[3],
+            bottom_left: border_glyphs[4],
+            bottom_right: border_glyphs[5],
+            color: match file.border.color {
+                Some(hex) => Some(parse_color(&hex).map_err(|err| format!("[border] {}", err))?),
+                None => None,
+            },
+        };
+
+        Ok(Theme {
+            fire: file.fire.into_ramp("fire", true)?,
+            scorch: file.scorch.into_ramp("scorch", false)?,
+            embers: file.embers.into_ramp("embers", true)?,
+            ash: file.ash.into_ramp("ash", true)?,
+            smoke: file.smoke.into_ramp("smoke", true)?,
+            border,
+        })
+    }
+
+    pub fn builtin(name: &str) -> Option<Self> {
+        BUILTIN_THEMES.iter()
+            .find(|&&(builtin_name, _)| builtin_name == name)
+            .map(|&(_, source)| Theme::parse(source).expect("built-in themes should be valid"))
+    }
+
+    /// load finds a theme by name or path. A name is looked up in the user's theme directory
+    /// (`~/.config/burn/themes/<name>.toml`) before the built-in themes, so built-ins can be overridden.
+    pub fn load(name_or_path: &str) -> Result<Self, String> {
+        let is_path = name_or_path.contains('/') || name_or_path.ends_with(".toml");
+
+        let path = if is_path {
+            Some(PathBuf::from(name_or_path))
+        } else {
+            themes_dir().map(|dir| dir.join(format!("{}.toml", name_or_path))).filter(|path| path.is_file())
+        };
+
+        match path {
+            Some(path) => Self::load_file(&path),
+            None => Self::builtin(name_or_path).ok_or_else(|| {
+                let builtins: Vec<&str> = BUILTIN_THEMES.iter().map(|&(name, _)| name).collect();
+                format!("no theme named `{}`; the built-in themes are {}", name_or_path, builtins.join(", "))
+            }),
+        }
+    }
+
+    fn load_file(path: &Path) -> Result<Self, String> {
+        let source = fs::read_to_string(path).map_err(|err| format!("could not read theme {}: {}", path.display(), err))?;
+        Self::parse(&source).map_err(|err| format!("invalid theme {}: {}", path.display(), err))
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::builtin(DEFAULT_THEME).unwrap()
+    }
+}
+
+fn themes_dir() -> Option<PathBuf> {
+    config::config_dir().map(|dir| dir.join("themes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Theme, BUILTIN_THEMES};
+    use termion::color::Rgb;
+
+    #[test]
+    fn test_builtin_themes_parse() {
+        for &(name, _) in BUILTIN_THEMES {
+            assert!(Theme::builtin(name).is_some());
+        }
+    }
+
+    #[test]
+    fn test_parse_theme() {
+        let source = r##"
+            [fire]
+            glyphs = ["a", "b"]
+            colors = ["#000000", "#ff0000"]
+            [scorch]
+            colors = ["#102030"]
+            [embers]
+            glyphs = ["."]
+            [ash]
+            glyphs = ["."]
+            [smoke]
+            glyphs = ["~"]
+            [border]
+            glyphs = "-|++++"
+            color = "#ffffff"
+        "##;
+
+        let theme = Theme::parse(source).unwrap();
+
+        assert_eq!(theme.fire.cell_at(1.0).glyph, 'b');
+        assert_eq!(theme.fire.color_at(0.5), Some(Rgb(128, 0, 0)));
+        assert_eq!(theme.embers.color_at(1.0), None);
+        assert_eq!(theme.border.color, Some(Rgb(255, 255, 255)));
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_themes() {
+        let classic = BUILTIN_THEMES[0].1;
+
+        assert!(Theme::parse(&classic.replace("#96140a", "red")).is_err());
+        assert!(Theme::parse(&classic.replace("━┃┏┓┗┛", "━┃")).is_err());
+        assert!(Theme::parse(&classic.replace("[smoke]", "[smoke]\nspeed = 2")).is_err());
+        assert!(Theme::parse("").is_err());
+    }
+}
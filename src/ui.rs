@@ -1,25 +1,125 @@
-use std::io::Write;
+use std::io::{self, Write};
+use std::mem;
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Once};
 
-use termion::{clear, cursor};
-
-type Field = Vec<Vec<u8>>;
+use backend::Backend;
+use color_depth::ColorDepth;
+use grid::Grid;
+use layers::Cell;
+use libc;
+use signal_hook::{consts, flag, SigId};
+use signal_hook::low_level;
+use termion::{clear, cursor, screen, style};
 
 const LINE_END: &[u8] = b"\r\n";
 
+/// Set while a full-screen `Ui` has the terminal.
+static TAKEN_OVER: Mutex<Option<TakenOver>> = Mutex::new(None);
+
+static INSTALL_PANIC_HOOK: Once = Once::new();
+
+struct TakenOver {
+    /// The terminal's settings from before, unless stdout isn't a terminal.
+    settings: Option<libc::termios>,
+    /// Whether the `Ui` switched to the alternate screen, and so has to switch back.
+    alternate_screen: bool,
+}
+
+/// Where on the screen frames are drawn.
+enum Placement {
+    /// From the top left corner.
+    Home,
+    /// In rows set aside below where the cursor was, where the last frame is left behind. `drawn` is whether the
+    /// cursor has been left at the bottom of a frame, rather than the top of the rows.
+    Inline { drawn: bool },
+}
+
+/// The termion backend: draws frames as text with escape codes, in colors `color_depth` can show, to a terminal or
+/// anything else that takes them, such as an asciicast.
 // TODO: move terminal size info into here?
 pub struct Ui<W: Write> {
   writer: W,
+  color_depth: ColorDepth,
+  /// Reused from one row to the next, so that drawing doesn't allocate.
+  line: Vec<u8>,
+  /// Set when SIGINT or SIGTERM arrives, for a full-screen `Ui`.
+  interrupted: Option<Arc<AtomicBool>>,
+  signals: Vec<SigId>,
+  placement: Placement,
 }
 
 impl<W: Write> Ui<W> {
-    pub fn create(writer: W) -> Self {
+    /// create draws to `writer`, which needn't be a terminal, from the top left corner of a cleared screen.
+    pub fn create(writer: W, color_depth: ColorDepth) -> Self {
         let mut ui = Ui {
-            writer: writer,
+            writer,
+            color_depth,
+            line: vec![],
+            interrupted: None,
+            signals: vec![],
+            placement: Placement::Home,
         };
 
         ui.setup_window();
 
-        return ui;
+        ui
+    }
+
+    /// fullscreen draws to `writer`, which should be the terminal on stdout, on the alternate screen. Keys typed
+    /// meanwhile aren't echoed, SIGINT and SIGTERM are caught rather than killing the process, and the terminal is put
+    /// back as it was when the `Ui` is dropped, even if that's because of a panic, leaving the scrollback untouched.
+    pub fn fullscreen(writer: W, color_depth: ColorDepth) -> io::Result<Self> {
+        let mut ui = Self::take_over(writer, color_depth, true, Placement::Home)?;
+
+        write!(ui.writer, "{}", screen::ToAlternateScreen)?;
+        ui.setup_window();
+
+        Ok(ui)
+    }
+
+    /// inline is like `fullscreen`, but rather than taking over the whole screen, it sets aside `rows` rows from the
+    /// cursor down, scrolling the terminal if need be, and leaves the last frame drawn there once it's dropped. `rows`
+    /// should be less than the height of the terminal.
+    pub fn inline(writer: W, rows: usize, color_depth: ColorDepth) -> io::Result<Self> {
+        let mut ui = Self::take_over(writer, color_depth, false, Placement::Inline { drawn: false })?;
+
+        // the newlines make sure there are enough rows below the cursor, and the cursor is then taken back up to the
+        // first of them
+        let newlines = vec![b'\n'; rows];
+        ui.writer.write_all(&newlines)?;
+        write!(ui.writer, "\r{}{}", cursor::Up(rows as u16), cursor::Hide)?;
+        ui.writer.flush()?;
+
+        Ok(ui)
+    }
+
+    fn take_over(writer: W, color_depth: ColorDepth, alternate_screen: bool, placement: Placement) -> io::Result<Self> {
+        let interrupted = Arc::new(AtomicBool::new(false));
+        let signals = vec![
+            flag::register(consts::SIGINT, Arc::clone(&interrupted))?,
+            flag::register(consts::SIGTERM, Arc::clone(&interrupted))?,
+        ];
+
+        INSTALL_PANIC_HOOK.call_once(|| {
+            let previous = panic::take_hook();
+            panic::set_hook(Box::new(move |info| {
+                restore_terminal();
+                previous(info);
+            }));
+        });
+
+        take_over_terminal(alternate_screen);
+
+        Ok(Ui {
+            writer,
+            color_depth,
+            line: vec![],
+            interrupted: Some(interrupted),
+            signals,
+            placement,
+        })
     }
 
     fn setup_window(&mut self) {
@@ -27,23 +127,122 @@ impl<W: Write> Ui<W> {
     }
 
     fn reset_window(&mut self) {
-        write!(self.writer, "{}", cursor::Show).unwrap();
+        // the terminal may have gone away, in which case there's nothing left to put back
+        let _ = write!(self.writer, "{}{}", style::Reset, cursor::Show).and_then(|_| self.writer.flush());
     }
+}
+
+impl<W: Write> Backend for Ui<W> {
+    fn draw(&mut self, frame: &Grid<Cell>) -> io::Result<()> {
+        match self.placement {
+            Placement::Home => write!(self.writer, "{}", cursor::Goto(1, 1))?,
+            Placement::Inline { ref mut drawn } => {
+                if *drawn {
+                    write!(self.writer, "\r{}", cursor::Up(frame.rows() as u16))?;
+                }
 
-    pub fn draw(&mut self, field: &Field) {
-        write!(self.writer, "{}", cursor::Goto(1, 1)).unwrap();
+                *drawn = true;
+            },
+        }
+
+        for row in frame.iter_rows() {
+            self.line.clear();
+            for cell in row {
+                self.color_depth.paint(&mut self.line, cell.glyph, cell.fg)?;
+            }
 
-        for line in field {
-            self.writer.write(&line).expect("failed to write line");
-            self.writer.write(LINE_END).unwrap();
+            self.writer.write_all(&self.line)?;
+            self.writer.write_all(LINE_END)?;
         }
 
-        self.writer.flush().unwrap();
+        self.writer.flush()
+    }
+
+    /// is_interrupted is whether SIGINT or SIGTERM has arrived, for a full-screen or inline `Ui`.
+    fn is_interrupted(&self) -> bool {
+        self.interrupted.as_ref().is_some_and(|interrupted| interrupted.load(Ordering::Relaxed))
+    }
+
+    /// read_key is the next key typed on stdin, for a full-screen or inline `Ui`, without waiting for one. Bytes that
+    /// aren't ASCII, such as parts of escape sequences for arrow keys, are passed over.
+    fn read_key(&mut self) -> Option<char> {
+        self.interrupted.as_ref()?;
+
+        loop {
+            let mut stdin = libc::pollfd { fd: libc::STDIN_FILENO, events: libc::POLLIN, revents: 0 };
+            if unsafe { libc::poll(&mut stdin, 1, 0) } <= 0 {
+                return None;
+            }
+
+            let mut byte = 0u8;
+            if unsafe { libc::read(libc::STDIN_FILENO, &mut byte as *mut u8 as *mut libc::c_void, 1) } != 1 {
+                return None;
+            }
+
+            if byte.is_ascii() {
+                return Some(byte as char);
+            }
+        }
     }
 }
 
 impl<W: Write> Drop for Ui<W> {
     fn drop(&mut self) {
         self.reset_window();
+
+        if self.interrupted.is_some() {
+            restore_terminal();
+        }
+
+        for signal in self.signals.drain(..) {
+            low_level::unregister(signal);
+        }
+    }
+}
+
+/// take_over_terminal saves the terminal's settings, if stdout is a terminal, then stops it echoing keys.
+fn take_over_terminal(alternate_screen: bool) {
+    let settings = unsafe {
+        let mut settings: libc::termios = mem::zeroed();
+
+        if libc::tcgetattr(libc::STDOUT_FILENO, &mut settings) == 0 {
+            let mut quiet = settings;
+            quiet.c_lflag &= !(libc::ECHO | libc::ICANON);
+            libc::tcsetattr(libc::STDOUT_FILENO, libc::TCSANOW, &quiet);
+
+            Some(settings)
+        } else {
+            None
+        }
+    };
+
+    *TAKEN_OVER.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(TakenOver { settings, alternate_screen });
+}
+
+/// restore_terminal leaves the alternate screen, if need be, and puts back the settings `take_over_terminal` saved, if a full-screen
+/// `Ui` is still in charge. It writes straight to stdout's file descriptor, since whatever is drawing may be holding
+/// the lock on `io::stdout`.
+fn restore_terminal() {
+    let mut taken_over = match TAKEN_OVER.try_lock() {
+        Ok(taken_over) => taken_over,
+        Err(_) => return,
+    };
+
+    let (settings, alternate_screen) = match taken_over.take() {
+        Some(TakenOver { settings, alternate_screen }) => (settings, alternate_screen),
+        None => return,
+    };
+
+    let mut reset = format!("{}{}", style::Reset, cursor::Show);
+    if alternate_screen {
+        reset += &screen::ToMainScreen.to_string();
+    }
+
+    unsafe {
+        libc::write(libc::STDOUT_FILENO, reset.as_ptr() as *const libc::c_void, reset.len());
+
+        if let Some(settings) = settings {
+            libc::tcsetattr(libc::STDOUT_FILENO, libc::TCSANOW, &settings);
+        }
     }
 }
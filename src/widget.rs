@@ -0,0 +1,136 @@
+use std::rc::Rc;
+
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::Color;
+use ratatui::widgets::Widget;
+
+use color_depth::ColorDepth;
+use config::Config;
+use grid::Grid;
+use layers::{BasicLayer, Cell, Compositor, Layerable};
+use state::CombustionState;
+use theme::Theme;
+
+/// Burns some text inside a ratatui layout. Unlike a `Scene`, there's no border, so the text can sit inside a `Block`
+/// or any other frame the app already draws.
+///
+/// The burn is a fixed size, set when it's made, and drawn from the top left corner of whatever area it's rendered
+/// into, cut off by the edges of that area. Rendering doesn't move the burn on; call `step` as often as ticks should
+/// pass, then render `&mut widget` as usual.
+pub struct BurnWidget {
+    text: BasicLayer,
+    state: CombustionState,
+    /// The whole burn as last rendered, kept so that rendering again doesn't allocate.
+    frame: Grid<Cell>,
+}
+
+impl BurnWidget {
+    /// new lays `text` out `rows` by `cols` cells, one line per row, and lights it, as `config` and `seed` have it.
+    /// There has to be at least one of each, for the fire to start in.
+    pub fn new(text: &str, rows: usize, cols: usize, config: &Config, seed: u64) -> Result<Self, String> {
+        if rows == 0 || cols == 0 {
+            return Err(format!("a {}x{} burn has no room for a fire", cols, rows));
+        }
+
+        let theme = Theme::load(&config.rendering.theme)?;
+        let lines = text.lines().map(|line| line.chars().map(|c| Some(Cell::plain(c))).collect()).collect();
+
+        let mut state = CombustionState::new(rows, cols, &config.simulation, seed);
+        state.set_theme(Rc::new(theme));
+        state.set_translucent_smoke(config.rendering.translucent_smoke);
+        state.set_flicker(config.rendering.flicker);
+        // with no border to keep them off of, smoke and ash can reach the edges
+        state.set_inset(0);
+        state.start_fire();
+
+        Ok(BurnWidget {
+            text: BasicLayer::create(rows, cols, lines),
+            state,
+            frame: Grid::new(rows, cols, Cell::plain(' ')),
+        })
+    }
+
+    /// step advances the burn by one tick.
+    pub fn step(&mut self) {
+        self.state.step();
+    }
+
+    /// is_over is whether the text has burned away and the smoke cleared.
+    pub fn is_over(&self) -> bool {
+        self.state.is_over()
+    }
+}
+
+impl Widget for &mut BurnWidget {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        self.state.render();
+
+        let compositor = Compositor {
+            rows: self.text.rows(),
+            cols: self.text.cols(),
+            color_depth: ColorDepth::TrueColor,
+        };
+        let layers: [&dyn Layerable; 4] = [&self.text, self.state.fire_layer(), self.state.ash_layer(), self.state.smoke_layer()];
+        compositor.composite_cells_into(&layers, &mut self.frame);
+
+        for i in 0..self.frame.rows().min(usize::from(area.height)) {
+            for j in 0..self.frame.cols().min(usize::from(area.width)) {
+                let cell = self.frame[(i, j)];
+                // cells without a color of their own keep the style the area already has
+                if let Some(target) = buf.cell_mut((area.x + j as u16, area.y + i as u16)) {
+                    target.set_char(cell.glyph);
+                    if let Some(fg) = cell.fg {
+                        target.set_fg(Color::Rgb(fg.0, fg.1, fg.2));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BurnWidget;
+    use config::Config;
+    use ratatui::buffer::Buffer;
+    use ratatui::layout::Rect;
+    use ratatui::widgets::Widget;
+
+    #[test]
+    fn test_renders_inside_its_area() {
+        let mut widget = BurnWidget::new("delete?\nyes", 2, 8, &Config::default(), 1).unwrap();
+        let mut buf = Buffer::empty(Rect::new(0, 0, 12, 4));
+
+        widget.render(Rect::new(2, 1, 5, 3), &mut buf);
+
+        let text: Vec<String> = (0..4).map(|y| (0..12).map(|x| buf[(x, y)].symbol()).collect()).collect();
+        assert_eq!(text[0], "            ");
+        assert_eq!(&text[1][..7], "  delet");
+        assert_eq!(&text[2][..5], "  yes");
+        assert_eq!(text[3], "            ");
+
+        while !widget.is_over() {
+            widget.step();
+        }
+
+        // with no border, ash settles on the widget's own bottom row
+        let mut buf = Buffer::empty(Rect::new(0, 0, 8, 2));
+        widget.render(Rect::new(0, 0, 8, 2), &mut buf);
+
+        let text: Vec<String> = (0..2).map(|y| (0..8).map(|x| buf[(x, y)].symbol()).collect()).collect();
+        assert_eq!(text[0], "        ");
+        assert_ne!(text[1], "        ");
+    }
+
+    #[test]
+    fn test_needs_room_to_burn() {
+        assert!(BurnWidget::new("", 0, 8, &Config::default(), 1).is_err());
+        assert!(BurnWidget::new("", 2, 0, &Config::default(), 1).is_err());
+
+        let mut widget = BurnWidget::new("x", 1, 1, &Config::default(), 1).unwrap();
+        while !widget.is_over() {
+            widget.step();
+        }
+    }
+}
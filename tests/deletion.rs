@@ -0,0 +1,72 @@
+//! Burns a real file with the `burn` binary, on a pseudo-terminal, to check that it's only deleted when asked to.
+
+extern crate libc;
+
+use std::fs;
+use std::io::Read;
+use std::os::unix::io::FromRawFd;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::ptr;
+use std::thread;
+
+/// burn_file writes a short note into a fresh directory and burns it with `args` and the env vars `vars` on a 40x12
+/// pseudo-terminal, returning whether the note is still there afterwards.
+fn burn_file(name: &str, args: &[&str], vars: &[(&str, &str)]) -> bool {
+    let dir: PathBuf = [env!("CARGO_TARGET_TMPDIR"), name].iter().collect();
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let note = dir.join("note.txt");
+    fs::write(&note, "burn after reading\n").unwrap();
+
+    // an empty config file keeps the user's own settings out of it
+    let config = dir.join("config.toml");
+    fs::write(&config, "").unwrap();
+
+    let (mut master, slave) = unsafe {
+        let (mut master, mut slave) = (0, 0);
+        let size = libc::winsize { ws_row: 12, ws_col: 40, ws_xpixel: 0, ws_ypixel: 0 };
+        assert_eq!(libc::openpty(&mut master, &mut slave, ptr::null_mut(), ptr::null(), &size), 0);
+        (fs::File::from_raw_fd(master), fs::File::from_raw_fd(slave))
+    };
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_burn"))
+        .args(args)
+        .arg(&note)
+        .args(["--duration", "200ms", "--seed", "1"])
+        .env("BURN_CONFIG", &config)
+        .env("BURN_RENDERING_OUTRO", "none")
+        .envs(vars.iter().cloned())
+        .stdin(Stdio::from(slave.try_clone().unwrap()))
+        .stdout(Stdio::from(slave.try_clone().unwrap()))
+        .stderr(Stdio::from(slave))
+        .spawn()
+        .unwrap();
+
+    // the terminal has to be drained, or the burn would stall once its buffer fills up
+    let output = thread::spawn(move || {
+        let mut output = vec![];
+        let _ = master.read_to_end(&mut output);
+        output
+    });
+
+    let status = child.wait().unwrap();
+    let output = output.join().unwrap();
+    assert!(status.success(), "burn failed with {}: {}", status, String::from_utf8_lossy(&output));
+
+    note.exists()
+}
+
+#[test]
+fn test_burning_keeps_the_file() {
+    assert!(burn_file("keeps", &[], &[]));
+
+    // deletion can't be switched on for every run from the environment
+    assert!(burn_file("keeps_despite_env", &[], &[("BURN_DELETION_ENABLED", "true")]));
+}
+
+#[test]
+fn test_delete_flag_deletes_the_file() {
+    assert!(!burn_file("deletes", &["--delete"], &[]));
+}
@@ -0,0 +1,107 @@
+//! Burns small, seeded pages and compares frames of them, as plain text, to the snapshots in `tests/snapshots`.
+//!
+//! After a change that's meant to alter how burns play out, look over the differences and accept them with
+//!
+//!     UPDATE_SNAPSHOTS=1 cargo test --test golden_frames
+//!
+//! which rewrites the snapshots in place, ready to be reviewed in `git diff` and committed. New snapshots are written
+//! the same way; without `UPDATE_SNAPSHOTS`, a missing snapshot fails the test.
+
+extern crate burn;
+
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+
+use burn::config::Config;
+use burn::grid::Grid;
+use burn::layers::Cell;
+use burn::scene::Scene;
+use burn::sim;
+use burn::state::SmokeModel;
+
+const ROWS: usize = 10;
+const COLS: usize = 24;
+
+/// Which ticks are captured, besides the last one. Each burn below should reach all of them; the quickest, with
+/// particle smoke, is over after 98.
+const FRAMES: [u64; 4] = [0, 10, 40, 80];
+
+const TEXT: &str = "It was a bright cold
+day in April, and the
+clocks were striking
+thirteen.";
+
+/// burn runs a page of `TEXT` to the end, writing out the frames at `FRAMES` and the last one.
+fn burn(smoke_model: SmokeModel, wind: f64, seed: u64) -> String {
+    let mut config = Config::default();
+    config.simulation.smoke_model = smoke_model;
+    config.simulation.wind = wind;
+
+    let mut scene = Scene::from_text(TEXT, ROWS, COLS, &config, seed).unwrap();
+    let mut frames = String::new();
+    let mut frame = Grid::new(ROWS, COLS, Cell::plain(' '));
+
+    loop {
+        let ticks = scene.ticks();
+
+        if FRAMES.contains(&ticks) || scene.is_over() {
+            writeln!(frames, "--- tick {} ---", ticks).unwrap();
+            frames.push_str(&sim::plain_text(&mut scene, ROWS, COLS, &mut frame));
+        }
+
+        if scene.is_over() {
+            return frames;
+        }
+
+        scene.step();
+    }
+}
+
+/// assert_snapshot compares `actual` to the snapshot called `name`, or writes it if asked to.
+fn assert_snapshot(name: &str, actual: &str) {
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "snapshots", &format!("{}.txt", name)].iter().collect();
+
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+
+    let expected = match fs::read_to_string(&path) {
+        Ok(expected) => expected,
+        Err(err) => panic!("couldn't read {}: {}\n\nto write it, rerun with UPDATE_SNAPSHOTS=1", path.display(), err),
+    };
+
+    if expected == actual {
+        return;
+    }
+
+    let (line, (want, got)) = expected.lines().chain(Some("<end>"))
+        .zip(actual.lines().chain(Some("<end>")))
+        .enumerate()
+        .find(|&(_, (want, got))| want != got)
+        .unwrap_or((0, ("", "")));
+
+    panic!(
+        "{} doesn't match, first at line {}:\n  expected: {:?}\n    actual: {:?}\n\n\
+         if the change is intended, rerun with UPDATE_SNAPSHOTS=1 and review the diff",
+        path.display(), line + 1, want, got,
+    );
+}
+
+#[test]
+fn test_particle_smoke() {
+    assert_snapshot("particles", &burn(SmokeModel::Particles, 0.0, 11));
+}
+
+#[test]
+fn test_fluid_smoke() {
+    assert_snapshot("fluid", &burn(SmokeModel::Fluid, 0.0, 11));
+}
+
+#[test]
+fn test_fluid_smoke_in_the_wind() {
+    assert_snapshot("fluid_wind", &burn(SmokeModel::Fluid, 0.6, 5));
+}